@@ -6,13 +6,20 @@ use std::ptr::read;
 use std::os::raw::{c_char, c_void};
 use std::mem::{transmute, transmute_copy, size_of};
 use std::ffi::{CStr, CString};
+use std::ops::Deref;
+use std::sync::PoisonError;
+use std::sync::atomic::Ordering;
+use std::borrow::Cow;
 
-use failure_derive::Fail;
+use std::error::Error;
+use std::fmt;
 
 use crate::types;
 use crate::types::Cell;
 use crate::consts::*;
-use crate::data::amx_functions;
+use crate::packed;
+use crate::args::{FromArgs, Parser};
+use crate::data::{amx_functions, amx_list, debug_hooks, allot_limit};
 
 pub type AmxResult<T> = Result<T, AmxError>;
 
@@ -44,15 +51,37 @@ macro_rules! call {
 }
 
 /// Gets a function from a raw pointer in `data::amx_functions`.
+///
+/// Returns `AmxError::NullExport` from the caller if there is no such function.
 macro_rules! import {
     ($type:ident) => {
         unsafe {
-            read(amx_functions.offset(Exports::$type as isize) as *const $crate::types::$type)
+            if amx_functions.is_null() {
+                return Err(AmxError::NullExport);
+            }
+
+            match read(amx_functions.offset(Exports::$type as isize) as *const Option<$crate::types::$type>) {
+                Some(func) => func,
+                None => return Err(AmxError::NullExport),
+            }
         }
     };
 }
 
 /// AMX struct that holds raw `types::AMX` pointer.
+///
+/// Functions which call the server return `AmxError::NullExport` until the plugin is loaded:
+///
+/// ```
+/// use samp_sdk::amx::{AMX, AmxError};
+///
+/// let amx = AMX::new(std::ptr::null_mut());
+///
+/// match amx.num_natives() {
+///     Err(AmxError::NullExport) => (),
+///     _ => panic!("AMX functions aren't set"),
+/// }
+/// ```
 pub struct AMX {
     pub amx: *mut types::AMX,
 }
@@ -100,6 +129,57 @@ impl AMX {
         call!(register(self.amx, ptr, len as i32) => ())
     }
 
+    /// Registers natives one by one and returns names of natives which the server has rejected.
+    ///
+    /// Unlike `register` it doesn't stop at the first error. `AmxError::NotFound` isn't reported,
+    /// because registering a single native returns it while the script has other unresolved natives.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use] extern crate samp_sdk;
+    /// use samp_sdk::types;
+    /// use samp_sdk::amx::AMX;
+    ///
+    /// extern "C" fn some_function(_: *mut types::AMX, _: *mut i32) -> i32 { 0 }
+    ///
+    /// fn amx_load(amx: &AMX) -> types::Cell {
+    ///     let natives = natives!{
+    ///         "SomeFunction" => some_function
+    ///     };
+    ///
+    ///     if let Err(failed) = amx.register_detailed(&natives) {
+    ///         for (name, err) in failed {
+    ///             log!("Can't register {}: {}", name, err);
+    ///         }
+    ///     }
+    ///
+    ///     0
+    /// }
+    /// ```
+    pub fn register_detailed(&self, natives: &[types::AMX_NATIVE_INFO]) -> Result<(), Vec<(String, AmxError)>> {
+        let mut failed = Vec::new();
+
+        for native in natives {
+            match self.register(::std::slice::from_ref(native)) {
+                Ok(()) | Err(AmxError::NotFound) => (),
+                Err(err) => {
+                    let name = unsafe {
+                        CStr::from_ptr(native.name).to_string_lossy().into_owned()
+                    };
+
+                    failed.push((name, err));
+                },
+            }
+        }
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(failed)
+        }
+    }
+
     /// Allocates memory cells inside AMX.
     ///
     /// # Return
@@ -123,15 +203,53 @@ impl AMX {
     ///     Ok(())
     /// }
     /// ```
+    ///
+    /// Returns `AmxError::Memory` without calling the server if `cells` is above the limit
+    /// set by `amx::set_allot_limit`.
     pub fn allot(&self, cells: usize) -> AmxResult<(Cell, usize)> {
-        let amx_addr = 0;
-        let phys_addr = 0;
+        let limit = allot_limit.load(Ordering::Relaxed);
+
+        if cells > limit {
+            log::warn!("Can't allot {} cells in AMX, the limit is {} cells", cells, limit);
+            return Err(AmxError::Memory);
+        }
+
+        let mut amx_addr = 0;
+        let mut phys_addr: *mut Cell = ::std::ptr::null_mut();
 
         let allot = import!(Allot);
 
-        unsafe {
-            call!(allot(self.amx, cells as i32, transmute(&amx_addr), transmute(&phys_addr)) => (amx_addr, phys_addr))
-        }
+        call!(allot(self.amx, cells as i32, &mut amx_addr, &mut phys_addr) => (amx_addr, phys_addr as usize))
+    }
+
+    /// Allocates memory cells inside AMX and returns a guard that releases them on drop.
+    ///
+    /// Since `release` frees all memory above the address, dropping a guard also frees
+    /// everything allocated after it.
+    ///
+    /// # Examples
+    /// ```
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    ///
+    /// fn call_with_reference(amx: &AMX) -> AmxResult<()> {
+    ///     let index = amx.find_public("OnReference")?;
+    ///     let allocation = amx.allot_guarded(1)?;
+    ///
+    ///     unsafe { *allocation.as_phys_ptr() = 655; }
+    ///     amx.push(allocation.amx_addr())?;
+    ///     amx.exec(index)?; // the memory is released even if exec fails
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn allot_guarded(&self, cells: usize) -> AmxResult<Allocation<'_>> {
+        let (amx_addr, phys_addr) = self.allot(cells)?;
+
+        Ok(Allocation {
+            amx: self,
+            amx_addr,
+            phys_addr,
+        })
     }
 
     /// Frees all memory **above** input address.
@@ -190,8 +308,84 @@ impl AMX {
         }
     }
 
+    /// Returns the size of the code section in bytes.
+    ///
+    /// See `mem_info`.
+    pub fn code_size(&self) -> AmxResult<usize> {
+        self.mem_info().map(|(code, _, _)| code as usize)
+    }
+
+    /// Returns the size of the data section in bytes.
+    ///
+    /// See `mem_info`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use] extern crate samp_sdk;
+    /// use samp_sdk::amx::AMX;
+    ///
+    /// fn log_memory_usage(amx: &AMX) {
+    ///     if let (Ok(data), Ok(stack_heap)) = (amx.data_size(), amx.stack_heap_size()) {
+    ///         log!("{} bytes of data, {} bytes of stack and heap", data, stack_heap);
+    ///     }
+    /// }
+    /// ```
+    pub fn data_size(&self) -> AmxResult<usize> {
+        self.mem_info().map(|(_, data, _)| data as usize)
+    }
+
+    /// Returns the size of the stack and heap in bytes.
+    ///
+    /// See `mem_info`.
+    pub fn stack_heap_size(&self) -> AmxResult<usize> {
+        self.mem_info().map(|(_, _, stack_heap)| stack_heap as usize)
+    }
+
+    /// Makes a copy of the AMX which shares its code, but has its own data, stack and heap.
+    ///
+    /// A clone can run publics of the script without touching the state of the original AMX.
+    ///
+    /// # Safety
+    /// `dest` must point to a zeroed `types::AMX` and `data` must point to a buffer of
+    /// at least data size plus stack and heap size bytes (see `mem_info`).
+    /// Both are owned by the caller and must outlive the clone, which also mustn't outlive
+    /// the original AMX, since the code section isn't copied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    /// use samp_sdk::types;
+    /// use std::mem::MaybeUninit;
+    ///
+    /// fn sandbox(amx: &AMX) -> AmxResult<(Box<MaybeUninit<types::AMX>>, Vec<u8>)> {
+    ///     let (_, data_size, stack_heap_size) = amx.mem_info()?;
+    ///
+    ///     let mut clone = Box::new(MaybeUninit::<types::AMX>::zeroed());
+    ///     let mut data = vec![0u8; (data_size + stack_heap_size) as usize];
+    ///
+    ///     unsafe {
+    ///         amx.clone_into(clone.as_mut_ptr(), data.as_mut_ptr() as *mut _)?;
+    ///     }
+    ///
+    ///     Ok((clone, data))
+    /// }
+    /// ```
+    pub unsafe fn clone_into(&self, dest: *mut types::AMX, data: *mut c_void) -> AmxResult<()> {
+        let clone = import!(Clone);
+        call!(clone(dest, self.amx, data) => ())
+    }
+
     /// Get an address of a reference value given to native.
     ///
+    /// The reference points right into AMX memory, so writes through it are seen by the script
+    /// and nothing has to be copied back or freed. It mustn't outlive the native call though:
+    /// the memory can move or be reused once the script continues.
+    ///
+    /// Returns `AmxError::MemoryAccess` if the address is outside of the data section and stack,
+    /// points to the unused space between heap and stack or isn't aligned to a cell.
+    ///
     /// # Examples
     ///
     /// ```
@@ -208,6 +402,8 @@ impl AMX {
     /// }
     /// ```
     pub fn get_address<'a, T: Sized>(&self, address: Cell) -> AmxResult<&'a mut T> {
+        self.check()?;
+
         unsafe {
             let header = (*self.amx).base as *const types::AMX_HEADER;
 
@@ -217,7 +413,9 @@ impl AMX {
                 (*self.amx).data as usize
             };
 
-            if address >= (*self.amx).hea && address < (*self.amx).stk || address < 0 || address >= (*self.amx).stp {
+            let misaligned = address % size_of::<Cell>() as Cell != 0;
+
+            if address >= (*self.amx).hea && address < (*self.amx).stk || address < 0 || address >= (*self.amx).stp || misaligned {
                 Err(AmxError::MemoryAccess)
             } else {
                 Ok(transmute(data + address as usize))
@@ -225,6 +423,192 @@ impl AMX {
         }
     }
 
+    /// Reads arguments of a raw native into a tuple, in the order the script passes them.
+    ///
+    /// Supports `Cell`, `bool`, `f32`, `Float`, `CString` and `String` (decoded from cp1251),
+    /// each of them takes one argument. Returns `AmxError::Params` if the script has passed fewer arguments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::amx::{AMX, AmxResult, AmxError};
+    /// use samp_sdk::types::Cell;
+    ///
+    /// // native: SetPlayerHealthEx(playerid, Float:health, bool:notify);
+    /// fn set_player_health_ex(amx: &AMX, params: *mut Cell) -> AmxResult<Cell> {
+    ///     let (player_id, health, notify): (Cell, f32, bool) = amx.read_args(params)?;
+    ///     Ok(player_id + health as Cell + notify as Cell)
+    /// }
+    ///
+    /// let amx = AMX::new(std::ptr::null_mut());
+    ///
+    /// let mut params = [12, 3, 50.0f32.to_bits() as Cell, 1];
+    /// assert_eq!(set_player_health_ex(&amx, params.as_mut_ptr()).unwrap(), 54);
+    ///
+    /// let mut params = [8, 3, 50.0f32.to_bits() as Cell];
+    /// match set_player_health_ex(&amx, params.as_mut_ptr()) {
+    ///     Err(AmxError::Params) => (),
+    ///     _ => panic!("notify isn't passed"),
+    /// }
+    /// ```
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    pub fn read_args<T: FromArgs>(&self, params: *mut Cell) -> AmxResult<T> {
+        let mut parser = Parser::new(params);
+        T::from_args(self, &mut parser)
+    }
+
+    /// Reads one cell of an AMX array without copying the whole array.
+    ///
+    /// Returns `AmxError::MemoryAccess` if the index is negative or the cell is out of AMX memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    /// use samp_sdk::types::Cell;
+    ///
+    /// // native: SwapFirstAndLast(array[], size);
+    /// fn swap_first_and_last(amx: &AMX, array: Cell, size: i32) -> AmxResult<Cell> {
+    ///     let first = amx.get_cell(array, 0)?;
+    ///     let last = amx.get_cell(array, size - 1)?;
+    ///
+    ///     amx.set_cell(array, 0, last)?;
+    ///     amx.set_cell(array, size - 1, first)?;
+    ///
+    ///     Ok(1)
+    /// }
+    /// ```
+    pub fn get_cell(&self, address: Cell, index: i32) -> AmxResult<Cell> {
+        self.cell_address(address, index).map(|cell| *cell)
+    }
+
+    /// Writes one cell of an AMX array.
+    ///
+    /// Returns `AmxError::MemoryAccess` if the index is negative or the cell is out of AMX memory.
+    pub fn set_cell(&self, address: Cell, index: i32, value: Cell) -> AmxResult<()> {
+        self.cell_address(address, index).map(|cell| *cell = value)
+    }
+
+    /// Writes cells to an AMX array at once and returns how many cells are written.
+    ///
+    /// The whole range is checked before writing, so nothing is written if it doesn't fit in AMX memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::testing::TestAmx;
+    /// # use samp_sdk::amx::AmxError;
+    ///
+    /// // A `values[4]` array at the address 0.
+    /// let test = TestAmx::new(&[0; 4], 16);
+    /// let amx = test.amx();
+    ///
+    /// assert_eq!(amx.write_cells(4, &[1, 2, 3]).unwrap(), 3);
+    /// assert_eq!(test.memory()[..4], [0, 1, 2, 3]);
+    /// #
+    /// # match amx.write_cells(8, &[4, 5, 6]) {
+    /// #     Err(AmxError::MemoryAccess) => (),
+    /// #     _ => panic!("the last cell is out of AMX memory"),
+    /// # }
+    /// #
+    /// # assert_eq!(test.memory()[..4], [0, 1, 2, 3]);
+    /// ```
+    pub fn write_cells(&self, address: Cell, cells: &[Cell]) -> AmxResult<usize> {
+        if cells.is_empty() {
+            return Ok(0);
+        }
+
+        let dest = self.range_address(address, cells.len())?;
+
+        unsafe {
+            ::std::ptr::copy_nonoverlapping(cells.as_ptr(), dest, cells.len());
+        }
+
+        Ok(cells.len())
+    }
+
+    /// Writes `Float:` values to an AMX array at once, see `write_cells`.
+    pub fn write_floats(&self, address: Cell, floats: &[f32]) -> AmxResult<usize> {
+        let cells: Vec<Cell> = floats.iter().map(|float| float.to_bits() as Cell).collect();
+        self.write_cells(address, &cells)
+    }
+
+    /// Reads an AMX array of a size known at compile time, e.g. coordinates or a color triplet.
+    ///
+    /// Returns `AmxError::MemoryAccess` if any of `N` cells is out of AMX memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::testing::TestAmx;
+    /// use samp_sdk::types::Cell;
+    /// # use samp_sdk::amx::AmxError;
+    ///
+    /// // A color triplet at the address 0 and a `Float:` value at 12.
+    /// let test = TestAmx::new(&[255, 128, 0, 1.5f32.to_bits() as Cell], 16);
+    /// let amx = test.amx();
+    ///
+    /// let color: [Cell; 3] = amx.read_cells(0).unwrap();
+    /// assert_eq!(color, [255, 128, 0]);
+    ///
+    /// let float: [f32; 1] = amx.read_floats(12).unwrap();
+    /// assert_eq!(float, [1.5]);
+    /// #
+    /// # match amx.read_cells::<3>(8) {
+    /// #     Err(AmxError::MemoryAccess) => (),
+    /// #     _ => panic!("the last cell is out of AMX memory"),
+    /// # }
+    /// ```
+    pub fn read_cells<const N: usize>(&self, address: Cell) -> AmxResult<[Cell; N]> {
+        let mut cells = [0; N];
+
+        if N != 0 {
+            let source = self.range_address(address, N)?;
+
+            unsafe {
+                ::std::ptr::copy_nonoverlapping(source, cells.as_mut_ptr(), N);
+            }
+        }
+
+        Ok(cells)
+    }
+
+    /// Reads an AMX array of `Float:` values of a size known at compile time, see `read_cells`.
+    pub fn read_floats<const N: usize>(&self, address: Cell) -> AmxResult<[f32; N]> {
+        self.read_cells::<N>(address).map(|cells| cells.map(|cell| f32::from_bits(cell as u32)))
+    }
+
+    /// Checks that `len` cells starting at `address` are in AMX memory and returns a pointer to the first one.
+    fn range_address(&self, address: Cell, len: usize) -> AmxResult<*mut Cell> {
+        if len == 0 || len > Cell::MAX as usize {
+            return Err(AmxError::MemoryAccess);
+        }
+
+        let last = (len as Cell - 1).checked_mul(size_of::<Cell>() as Cell)
+            .and_then(|offset| address.checked_add(offset))
+            .ok_or(AmxError::MemoryAccess)?;
+
+        // The range mustn't cross the unused space between heap and stack.
+        if address < self.hea() && last >= self.stk() {
+            return Err(AmxError::MemoryAccess);
+        }
+
+        self.get_address::<Cell>(last)?;
+        self.get_address::<Cell>(address).map(|first| first as *mut Cell)
+    }
+
+    fn cell_address(&self, address: Cell, index: i32) -> AmxResult<&mut Cell> {
+        if index < 0 {
+            return Err(AmxError::MemoryAccess);
+        }
+
+        let address = index.checked_mul(size_of::<Cell>() as i32)
+            .and_then(|offset| address.checked_add(offset))
+            .ok_or(AmxError::MemoryAccess)?;
+
+        self.get_address(address)
+    }
+
     /// Pushes a primitive value or an address to AMX stack.
     ///
     /// # Examples
@@ -268,7 +652,25 @@ impl AMX {
     ///     Ok(())
     /// }
     /// ```
+    ///
+    /// Every element is written to its own cell, so elements must have the size of a cell.
+    /// Otherwise `AmxError::Format` is returned, `push_cells` and `push_floats` don't need the check:
+    ///
+    /// ```
+    /// use samp_sdk::amx::{AMX, AmxError};
+    ///
+    /// let amx = AMX::new(std::ptr::null_mut());
+    ///
+    /// match amx.push_array(&[1u8, 2, 3]) {
+    ///     Err(AmxError::Format) => (),
+    ///     _ => panic!("bytes can't be pushed as cells"),
+    /// }
+    /// ```
     pub fn push_array<T: Sized>(&self, array: &[T]) -> AmxResult<Cell> {
+        if size_of::<T>() != size_of::<Cell>() {
+            return Err(AmxError::Format);
+        }
+
         let (amx_addr, phys_addr) = self.allot(array.len())?;
         let dest = phys_addr as *mut Cell;
 
@@ -282,12 +684,62 @@ impl AMX {
         Ok(amx_addr)
     }
 
+    /// Allots memory for cells, copies them and pushes the address to the AMX stack.
+    ///
+    /// Returns the address to release after `exec`, like `push_array`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::testing::TestAmx;
+    /// use samp_sdk::types::Cell;
+    ///
+    /// let test = TestAmx::new(&[], 64);
+    /// let amx = test.amx();
+    ///
+    /// let ints = amx.push_cells(&[1, 2, 3]).unwrap();
+    /// let floats = amx.push_floats(&[0.5, -1.0]).unwrap();
+    ///
+    /// assert_eq!(*amx.get_address::<[Cell; 3]>(ints).unwrap(), [1, 2, 3]);
+    /// assert_eq!(*amx.get_address::<[f32; 2]>(floats).unwrap(), [0.5, -1.0]);
+    ///
+    /// amx.release(ints).unwrap();
+    /// ```
+    pub fn push_cells(&self, cells: &[Cell]) -> AmxResult<Cell> {
+        let (amx_addr, phys_addr) = self.allot(cells.len())?;
+
+        unsafe {
+            ::std::ptr::copy_nonoverlapping(cells.as_ptr(), phys_addr as *mut Cell, cells.len());
+        }
+
+        self.push(amx_addr)?;
+        Ok(amx_addr)
+    }
+
+    /// Allots memory for `Float:` values and pushes the address to the AMX stack, see `push_cells`.
+    pub fn push_floats(&self, floats: &[f32]) -> AmxResult<Cell> {
+        let cells: Vec<Cell> = floats.iter().map(|float| float.to_bits() as Cell).collect();
+        self.push_cells(&cells)
+    }
+
     /// Allots memory for a string and pushes it to the AMX stack.
     ///
+    /// Bytes of the string are copied as is, without any re-encoding.
+    /// A packed string keeps four characters in a cell, the first one in the most significant byte.
+    ///
     /// Please, don't use it directly! Better use macros `exec!`, `exec_public!` and `exec_native!`.
     pub fn push_string(&self, string: &CStr, packed: bool) -> AmxResult<Cell> {
         if packed {
-            unimplemented!()
+            let cells = packed::pack(string.to_bytes());
+            let (amx_addr, phys_addr) = self.allot(cells.len())?;
+            let dest = phys_addr as *mut Cell;
+
+            unsafe {
+                ::std::ptr::copy_nonoverlapping(cells.as_ptr(), dest, cells.len());
+            }
+
+            self.push(amx_addr)?;
+            Ok(amx_addr)
         } else {
             let bytes = string.to_bytes_with_nul();
             let (amx_addr, phys_addr) = self.allot(bytes.len())?;
@@ -304,6 +756,47 @@ impl AMX {
         }
     }
 
+    /// Converts a 16-bit value read from an AMX file to the byte order of the host, in place.
+    ///
+    /// AMX files are little-endian, so it does nothing on x86, but the server swaps bytes on big-endian builds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::testing::TestAmx;
+    /// # use samp_sdk::consts::Exports;
+    /// # assert_eq!(Exports::Align16 as usize, 0);
+    /// # assert_eq!(Exports::Align32 as usize, 1);
+    ///
+    /// let test = TestAmx::new(&[], 16);
+    /// let amx = test.amx();
+    ///
+    /// // The first bytes of an AMX file: the magic. Little-endian hosts leave it as it is.
+    /// let mut magic = u16::from_le_bytes([0xE0, 0xF1]);
+    /// amx.align16(&mut magic).unwrap();
+    /// assert_eq!(magic, 0xF1E0);
+    ///
+    /// let mut size = u32::from_le_bytes([0x00, 0x01, 0x00, 0x00]);
+    /// amx.align32(&mut size).unwrap();
+    /// assert_eq!(size, 256);
+    /// ```
+    pub fn align16(&self, value: &mut u16) -> AmxResult<()> {
+        let align16 = import!(Align16);
+        align16(value as *mut u16);
+        Ok(())
+    }
+
+    /// Converts a 32-bit value read from an AMX file to the byte order of the host, in place.
+    ///
+    /// See `align16`. Cells of AMX memory are already in the host order, so values read with
+    /// `get_cell` or `get_address` don't need it. Neither do packed strings: `packed::pack` and `unpack`
+    /// work on cell values, not on bytes of a file, and swapping them would break them on big-endian hosts.
+    pub fn align32(&self, value: &mut u32) -> AmxResult<()> {
+        let align32 = import!(Align32);
+        align32(value as *mut u32);
+        Ok(())
+    }
+
     /// Execs an AMX function.
     ///
     /// # Examples
@@ -322,38 +815,256 @@ impl AMX {
     ///     }
     /// }
     /// ```
+    ///
+    /// An error is returned as `AmxError::WithContext` with the name of the public
+    /// and the instruction pointer where the execution has stopped.
+    /// Use `AmxError::kind()` to get the original error.
+    ///
+    /// ```
+    /// use samp_sdk::amx::AmxError;
+    /// use samp_sdk::testing::TestAmx;
+    ///
+    /// let mut test = TestAmx::new(&[], 16);
+    ///
+    /// let index = test.add_public("OnPlayerUpdate", |amx, _| {
+    ///     // The script stops at this instruction.
+    ///     unsafe { (*amx.amx).cip = 0x40; }
+    ///     let _ = amx.raise_error(AmxError::Bounds);
+    ///     1
+    /// });
+    ///
+    /// match test.amx().exec(index) {
+    ///     Err(AmxError::WithContext { kind, public, cip }) => {
+    ///         assert!(matches!(*kind, AmxError::Bounds));
+    ///         assert_eq!(public.as_deref(), Some("OnPlayerUpdate"));
+    ///         assert_eq!(cip, Some(0x40));
+    ///     }
+    ///     _ => panic!("the public has raised an error"),
+    /// }
+    /// ```
+    ///
+    /// A negative index is `AmxError::Index`, so an index from an unchecked lookup
+    /// isn't passed to the server. Use `exec_main` and `exec_continue` for the special indices.
+    ///
+    /// ```
+    /// use samp_sdk::amx::{AMX, AmxError};
+    ///
+    /// let amx = AMX::new(std::ptr::null_mut());
+    ///
+    /// match amx.exec(-1) {
+    ///     Err(AmxError::Index) => (),
+    ///     _ => panic!("-1 isn't an index of a public"),
+    /// }
+    /// ```
     pub fn exec(&self, index: i32) -> AmxResult<i32> {
-        let exec = import!(Exec);
-
-        let retval = -1;
-        unsafe {
-            call!(exec(self.amx, transmute(&retval), index) => retval)
+        if index < 0 {
+            return Err(AmxError::Index);
         }
+
+        self.exec_index(index)
     }
 
-    /// Returns an index of a public by its name.
+    /// Execs `main` of the script.
+    pub fn exec_main(&self) -> AmxResult<i32> {
+        self.exec_index(AMX_EXEC_MAIN)
+    }
+
+    /// Continues the script after it has been put to sleep, see `AmxError::Sleep`.
+    ///
+    /// Errors of `exec` come with a context, so `Sleep` is matched on `AmxError::kind()`.
     ///
     /// # Examples
     ///
     /// ```
-    /// use samp_sdk::amx::AMX;
+    /// use samp_sdk::amx::{AMX, AmxError, AmxResult};
+    ///
+    /// fn exec_to_end(amx: &AMX, index: i32) -> AmxResult<i32> {
+    ///     let mut result = amx.exec(index);
     ///
-    /// fn hasOnPlayerConnect(amx: &AMX) -> bool {
-    ///     let public_index = amx.find_public("OnPlayerConnect").unwrap();
-    ///     public_index >= 0
+    ///     loop {
+    ///         match result {
+    ///             Err(ref err) if matches!(err.kind(), AmxError::Sleep) => result = amx.exec_continue(),
+    ///             result => return result,
+    ///         }
+    ///     }
     /// }
     /// ```
-    pub fn find_public(&self, name: &str) -> AmxResult<i32> {
-        let find_public = import!(FindPublic);
+    pub fn exec_continue(&self) -> AmxResult<i32> {
+        self.exec_index(AMX_EXEC_CONT)
+    }
 
-        let index = -1;
-        let c_name = CString::new(name).unwrap();
+    fn exec_index(&self, index: i32) -> AmxResult<i32> {
+        let exec = import!(Exec);
+        self.check()?;
 
-        unsafe {
-            call!(find_public(self.amx, c_name.as_ptr(), transmute(&index)) => index)
+        let mut retval: Cell = 0;
+        let result = exec(self.amx, &mut retval, index);
+
+        if result == 0 {
+            Ok(retval)
+        } else {
+            let public = if index >= 0 {
+                self.get_public(index).ok().map(|name| name.to_string_lossy().into_owned())
+            } else {
+                None
+            };
+
+            Err(AmxError::WithContext {
+                kind: Box::new(AmxError::from(result)),
+                public,
+                cip: Some(self.cip()),
+            })
+        }
+    }
+
+    /// Execs an AMX function which returns a `Float:` value.
+    ///
+    /// See `exec`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    ///
+    /// fn player_health(amx: &AMX, player_id: i32) -> AmxResult<f32> {
+    ///     let index = amx.find_public("GetPlayerHealthEx")?;
+    ///     amx.push(player_id)?;
+    ///     amx.exec_float(index)
+    /// }
+    /// ```
+    pub fn exec_float(&self, index: i32) -> AmxResult<f32> {
+        self.exec(index).map(|retval| f32::from_bits(retval as u32))
+    }
+
+    /// Execs an AMX function and converts the returned cell to `T`.
+    ///
+    /// See `exec`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    /// use samp_sdk::types::Float;
+    ///
+    /// fn player_armour(amx: &AMX, player_id: i32) -> AmxResult<Float> {
+    ///     let index = amx.find_public("GetPlayerArmourEx")?;
+    ///     amx.push(player_id)?;
+    ///     amx.exec_as(index)
+    /// }
+    /// ```
+    pub fn exec_as<T: From<Cell>>(&self, index: i32) -> AmxResult<T> {
+        self.exec(index).map(T::from)
+    }
+
+    /// Makes a builder that calls a public with given arguments.
+    ///
+    /// Arguments are added in the same order as in the public's declaration,
+    /// the builder pushes them in reverse and releases all allocated memory after the call.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    /// use samp_sdk::types::Cell;
+    /// use std::ffi::CString;
+    ///
+    /// // forward OnPlayerDataChanged(playerid, const name[], data[], size);
+    /// fn notify(amx: &AMX, player_id: i32) -> AmxResult<Cell> {
+    ///     let name = CString::new("Name_Surname").unwrap();
+    ///     let data = vec![1, 2, 3];
+    ///
+    ///     amx.call_public("OnPlayerDataChanged")
+    ///         .arg(player_id)
+    ///         .arg_string(&name)
+    ///         .arg_array(&data)
+    ///         .arg(data.len() as i32)
+    ///         .exec()
+    /// }
+    /// ```
+    pub fn call_public(&self, name: &str) -> PublicCall<'_> {
+        PublicCall {
+            amx: self,
+            index: self.find_public(name),
+            args: Vec::new(),
+            outs: Vec::new(),
+        }
+    }
+
+    /// Makes a builder that calls a public by its index.
+    ///
+    /// See `call_public`.
+    pub fn call_public_index(&self, index: i32) -> PublicCall<'_> {
+        PublicCall {
+            amx: self,
+            index: Ok(index),
+            args: Vec::new(),
+            outs: Vec::new(),
+        }
+    }
+
+    /// Returns an index of a public by its name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    /// use samp_sdk::types::Cell;
+    ///
+    /// fn call_on_player_connect(amx: &AMX, player_id: i32) -> AmxResult<Cell> {
+    ///     let index = amx.find_public("OnPlayerConnect")?;
+    ///
+    ///     amx.push(player_id)?;
+    ///     amx.exec(index)
+    /// }
+    /// ```
+    ///
+    /// Returns `AmxError::NotFound` if there is no such public, see `try_find_public` to check it.
+    pub fn find_public(&self, name: &str) -> AmxResult<i32> {
+        let find_public = import!(FindPublic);
+
+        let index = -1;
+        let c_name = CString::new(name).unwrap();
+
+        unsafe {
+            call!(find_public(self.amx, c_name.as_ptr(), transmute(&index)) => index)
+        }
+    }
+
+    /// Returns an index of a public by its name or `None` if the script doesn't have it.
+    ///
+    /// Other errors are still returned as `Err`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    /// use samp_sdk::types::Cell;
+    ///
+    /// fn notify_connect(amx: &AMX, player_id: i32) -> AmxResult<Option<Cell>> {
+    ///     match amx.try_find_public("OnPlayerConnect")? {
+    ///         Some(index) => {
+    ///             amx.push(player_id)?;
+    ///             amx.exec(index).map(Some)
+    ///         }
+    ///         None => Ok(None),
+    ///     }
+    /// }
+    /// ```
+    pub fn try_find_public(&self, name: &str) -> AmxResult<Option<i32>> {
+        match self.find_public(name) {
+            Ok(index) => Ok(Some(index)),
+            Err(AmxError::NotFound) => Ok(None),
+            Err(err) => Err(err),
         }
     }
 
+    /// Checks if the script has a public with this name.
+    ///
+    /// Errors are treated as if there is no such public.
+    pub fn has_public(&self, name: &str) -> bool {
+        self.try_find_public(name).ok().flatten().is_some()
+    }
+
     /// Returns an index of a native by its name.
     ///
     /// # Examples
@@ -369,6 +1080,17 @@ impl AMX {
         }
     }
 
+    /// Returns an index of a native by its name or `None` if the script doesn't use it.
+    ///
+    /// See `try_find_public`.
+    pub fn try_find_native(&self, name: &str) -> AmxResult<Option<i32>> {
+        match self.find_native(name) {
+            Ok(index) => Ok(Some(index)),
+            Err(AmxError::NotFound) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
     /// Returns a pointer to a public variable.
     pub fn find_pubvar<T: Sized>(&self, name: &str) -> AmxResult<&mut T> {
         let find_pubvar = import!(FindPubVar);
@@ -398,224 +1120,587 @@ impl AMX {
         }
     }
 
-    /// Get a name of a native by its index.
-    pub fn get_native(&self, index: i32) -> AmxResult<CString> {
-        let get_native = import!(GetNative);
-
-        let value = CString::new(vec![1; 32]).unwrap();
+    /// Get a number of AMX publics.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::amx::AMX;
+    ///
+    /// fn has_publics(amx: &AMX) -> bool {
+    ///     amx.num_publics().map(|count| count > 0).unwrap_or(false)
+    /// }
+    /// ```
+    pub fn num_publics(&self) -> AmxResult<i32> {
+        let num_publics = import!(NumPublics);
 
-        let ptr = value.into_raw();
+        let mut value: i32 = -1;
 
-        unsafe {
-            call!(get_native(self.amx, index, ptr) => CString::from_raw(ptr))
-        }
+        call!(num_publics(self.amx, &mut value) => value)
     }
 
-    /// Get a name of a public by its index.
-    #[inline(always)]
-    pub fn get_public(&self, index: i32) -> AmxResult<&CStr> {
-        let name = unsafe {
-            let header = self.header();
-
-            if header.is_null() {
-                return Err(AmxError::Memory);
-            }
-
-            let ptr = header as i32 + (*header).publics + index * (*header).defsize as i32;
+    /// Get a number of AMX public variables.
+    pub fn num_pubvars(&self) -> AmxResult<i32> {
+        let num_pubvars = import!(NumPubVars);
 
-            let func = ptr as *const c_void;
+        let mut value: i32 = -1;
 
-            if func.is_null() {
-                return Err(AmxError::Memory);
-            }
+        call!(num_pubvars(self.amx, &mut value) => value)
+    }
 
-            let str_ptr = if (*header).defsize as usize == size_of::<types::FUNCSTUBNT>() {
-                (header as u32 + (*(func as *const types::FUNCSTUBNT)).nameofs) as *const c_char
-            } else {
-                (*(func as *const types::AMX_FUNCSTUB)).name.as_ptr() as *const c_char
-            };
+    /// Get a number of AMX tags.
+    pub fn num_tags(&self) -> AmxResult<i32> {
+        let num_tags = import!(NumTags);
 
-            CStr::from_ptr(str_ptr)
-        };
+        let mut value: i32 = -1;
 
-        Ok(name)
+        call!(num_tags(self.amx, &mut value) => value)
     }
 
-    pub fn get_native_addr(&self, index: i32) -> AmxResult<usize> {
-        let header = self.header();
-       
-        let amx_addr = unsafe {
-            (*((header as i32 + (*header).natives + (*header).defsize as i32 * index) as *const types::AMX_FUNCSTUB)).address
-        };
+    /// Get a name and an id of a tag by its index.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::amx::AMX;
+    ///
+    /// fn print_tags(amx: &AMX) {
+    ///     let count = amx.num_tags().unwrap_or(0);
+    ///
+    ///     for index in 0..count {
+    ///         if let Ok((name, tag_id)) = amx.get_tag(index) {
+    ///             println!("{}: {:#x}", name, tag_id);
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn get_tag(&self, index: i32) -> AmxResult<(String, Cell)> {
+        let get_tag = import!(GetTag);
 
-        Ok(amx_addr as usize)
-    }
+        let mut buffer = vec![0u8; AMX_NAME_MAX + 1];
+        let mut tag_id: Cell = 0;
 
-    #[inline(always)]
-    pub fn header(&self) -> *const types::AMX_HEADER {
         unsafe {
-            (*self.amx).base as _
+            call!(get_tag(self.amx, index, buffer.as_mut_ptr() as *mut i8, &mut tag_id) => {
+                (CStr::from_ptr(buffer.as_ptr() as *const c_char).to_string_lossy().into_owned(), tag_id)
+            })
         }
     }
 
-    /// Gets length of a string.
-    pub fn string_len(&self, address: *const Cell) -> AmxResult<usize> {
-        let string_len = import!(StrLen);
-        let mut length = 0;
-
-        call!(string_len(address, &mut length) => length as usize)
-    }
-
-    /// Gets a string from AMX.
+    /// Get a name of a tag by its id.
     ///
-    /// Deprecated due to its assumption of cp1251 encoding. Use AMX::get_cstring_of_length().
+    /// Tag ids are passed to natives declared with `{Float, _}:...`-like arguments
+    /// and can be read from `tagof` in Pawn.
     ///
     /// # Examples
     ///
     /// ```
-    /// #[macro_use] extern crate samp_sdk;
     /// use samp_sdk::amx::{AMX, AmxResult};
     /// use samp_sdk::types::Cell;
     ///
-    /// pub struct MyPlugin;
-    ///
-    /// impl MyPlugin {
-    ///
-    ///     fn raw_function(&self, amx: &AMX, params: *mut Cell) -> AmxResult<Cell> {
-    ///         unsafe {
-    ///             let ptr = std::ptr::read(params.offset(1));
-    ///             let mut addr = amx.get_address::<i32>(ptr)?; // get a pointer from amx
-    ///             let len = amx.string_len(addr)?; // get string length in amx
-    ///             let string = amx.get_string_of_length(addr, len + 1)?; // convert amx string to rust CString
-    ///
-    ///             log!("got string: {}", string);
-    ///
-    ///             std::mem::forget(addr);
-    ///         }
-    ///
-    ///         Ok(0)
-    ///     }
-    ///
+    /// fn is_float(amx: &AMX, tag_id: Cell) -> AmxResult<bool> {
+    ///     amx.find_tag_id(tag_id).map(|name| name == "Float")
     /// }
     /// ```
-    #[deprecated(note = "Deprecated due to its assumption of cp1251 encoding. Use AMX::get_cstring_of_length().")]
-    pub unsafe fn get_string_of_length(&self, address: *const Cell, size: usize) -> AmxResult<String> {
-        let cstring = self.get_cstring_of_length(address,size);
-        crate::cp1251::decode(cstring.as_bytes())
+    pub fn find_tag_id(&self, tag_id: Cell) -> AmxResult<String> {
+        let find_tag_id = import!(FindTagId);
+
+        let mut buffer = vec![0u8; AMX_NAME_MAX + 1];
+
+        unsafe {
+            call!(find_tag_id(self.amx, tag_id, buffer.as_mut_ptr() as *mut i8) => {
+                CStr::from_ptr(buffer.as_ptr() as *const c_char).to_string_lossy().into_owned()
+            })
+        }
     }
 
-    /// Gets a string from a raw pointer to `Cell`.
+    /// Get a name of a native by its index.
     ///
-    /// Deprecated due to its assumption of cp1251 encoding. Use AMX::get_cstring().
+    /// Returns `AmxError::Index` if there is no native with such index.
+    ///
+    /// The server copies the whole name, so its length is taken from the header first:
+    /// names up to `AMX_NAME_MAX` are read into a buffer on the stack, longer ones into a heap buffer.
     ///
     /// # Examples
+    ///
     /// ```
-    /// use samp_sdk::log;
-    /// use samp_sdk::types::Cell;
-    /// use samp_sdk::amx::{AMX, AmxResult};
+    /// use samp_sdk::amx::AmxError;
+    /// use samp_sdk::testing::TestAmx;
     ///
-    /// // native:PushString(const string[]);
-    /// fn raw_arguments(amx: &AMX, args: *mut Cell) -> AmxResult<Cell> {
-    ///     let string = amx.get_string( unsafe { args.offset(1) } )?;
-    ///     log!("got a string: {}", string);
-    ///     Ok(0)
+    /// let mut test = TestAmx::new(&[], 16);
+    /// test.add_native("SetPlayerHealth");
+    ///
+    /// let amx = test.amx();
+    /// assert_eq!(amx.get_native(0).unwrap().to_str(), Ok("SetPlayerHealth"));
+    ///
+    /// match amx.get_native(1) {
+    ///     Err(AmxError::Index) => (),
+    ///     _ => panic!("there is only one native"),
     /// }
     /// ```
-    #[deprecated(note = "Deprecated due to its assumption of cp1251 encoding. Use AMX::get_cstring().")]
-    pub fn get_string(&self, cell: *mut Cell) -> AmxResult<String> {
-        let cstring = self.get_cstring(cell)?;
-        crate::cp1251::decode(cstring.as_bytes())
+    pub fn get_native(&self, index: i32) -> AmxResult<CString> {
+        let get_native = import!(GetNative);
+
+        let len = self.stub_name(|header| (header.natives, header.libraries), index)?.to_bytes().len();
+
+        let mut stack = [0u8; AMX_NAME_MAX + 1];
+        let mut heap = Vec::new();
+
+        let buffer = if len <= AMX_NAME_MAX {
+            &mut stack[..]
+        } else {
+            heap.resize(len + 1, 0u8);
+            &mut heap[..]
+        };
+
+        call!(get_native(self.amx, index, buffer.as_mut_ptr() as *mut i8) => {
+            let len = buffer.iter().position(|&byte| byte == 0).unwrap_or(len);
+            // There are no null bytes before `len`.
+            CString::new(&buffer[..len]).unwrap()
+        })
     }
 
-    /// Gets a CString from AMX, given a cell address and length. Mostly for internal use.
-    /// You are better off using AMX::get_cstring() if you only have the Cell containing the string.
-    /// 
-    /// Since SA-MP does not support Unicode and relies on Windows codepages,
-    /// this function will work correctly for all servers, including the ones which use (or whose users use)
-    /// codepages other than cp1251.
-    /// 
-    /// It is also faster, because it does not have to unnecessarily validate and convert SA-MP's strings to UTF8. 
+    /// Get a name of a public by its index.
     ///
-    /// # Examples
+    /// Returns `AmxError::Index` if there is no public with such index.
     ///
-    /// ```
-    /// #[macro_use] extern crate samp_sdk;
-    /// use samp_sdk::amx::{AMX, AmxResult};
-    /// use samp_sdk::types::Cell;
-    /// use samp_sdk::cp1251;
+    /// # Examples
     ///
-    /// pub struct MyPlugin;
+    /// Names are read from the name table or from the entries, depending on the header:
     ///
-    /// impl MyPlugin {
+    /// ```
+    /// use samp_sdk::amx::AmxError;
+    /// use samp_sdk::testing::TestAmx;
     ///
-    ///     fn raw_function(&self, amx: &AMX, params: *mut Cell) -> AmxResult<Cell> {
-    ///         unsafe {
-    ///             let ptr = std::ptr::read(params.offset(1));
-    ///             let mut addr = amx.get_address::<i32>(ptr)?; // get a pointer from amx
-    ///             let len = amx.string_len(addr)?; // get string length in amx
-    ///             let string = unsafe { 
-    ///                 amx.get_cstring_of_length(addr, len + 1) 
-    ///             };
+    /// for mut test in vec![TestAmx::new(&[], 16), TestAmx::with_inline_names(&[], 16)] {
+    ///     test.add_public("OnGameModeInit", |_, _| 1);
+    ///     test.add_public("OnPlayerConnect", |_, _| 1);
     ///
-    ///             log!("got CString: {:?}, converted to UTF-8 Rust String: {:?}", string, cp1251::decode(string.to_bytes()));
+    ///     let amx = test.amx();
+    ///     let index = amx.find_public("OnPlayerConnect").unwrap();
     ///
-    ///             std::mem::forget(addr);
-    ///         }
+    ///     assert_eq!(amx.get_public(index).unwrap().to_str(), Ok("OnPlayerConnect"));
+    ///     assert_eq!(amx.get_public(0).unwrap().to_str(), Ok("OnGameModeInit"));
     ///
-    ///         Ok(0)
+    ///     match amx.get_public(2) {
+    ///         Err(AmxError::Index) => (),
+    ///         _ => panic!("there are only two publics"),
     ///     }
     ///
+    ///     match amx.get_public(-1) {
+    ///         Err(AmxError::Index) => (),
+    ///         _ => panic!("a negative index"),
+    ///     }
     /// }
+    ///
+    /// # let test = TestAmx::new(&[], 16);
+    /// # let amx = test.amx();
+    /// # let header = amx.header() as *mut samp_sdk::types::AMX_HEADER;
+    /// # unsafe { (*header).defsize = 0; }
+    /// # assert!(matches!(amx.get_public(0), Err(AmxError::Format)));
+    /// # assert!(matches!(amx.get_native_addr(0), Err(AmxError::Format)));
+    /// # unsafe { (*header).defsize = 8; (*header).natives = (*header).publics - 8; }
+    /// # assert!(matches!(amx.get_public(0), Err(AmxError::Format)));
     /// ```
-    pub unsafe fn get_cstring_of_length(&self, address: *const Cell, size: usize) -> CString {
-        const UNPACKEDMAX: u32 = ((1u32 << ((size_of::<u32>() - 1) * 8)) - 1u32);
-        const CHARBITS: usize = 8 * size_of::<u8>();
+    #[inline(always)]
+    pub fn get_public(&self, index: i32) -> AmxResult<&CStr> {
+        self.stub_name(|header| (header.publics, header.natives), index)
+    }
 
-        let mut string = Vec::with_capacity(size);
+    /// Returns a pointer to an entry of a header table, `table` gives offsets where it starts and ends.
+    ///
+    /// Returns `AmxError::Format` if the header has an unknown `defsize` or the table has bad bounds.
+    fn stub(&self, table: fn(&types::AMX_HEADER) -> (i32, i32), index: i32) -> AmxResult<*mut u8> {
+        self.check()?;
 
-        if read(address) as u32 > UNPACKEDMAX {
-            // packed string
-            let mut i = size_of::<Cell>() - 1;
-            let mut cell = 0;
-            let mut ch;
-            let mut length = 0;
-            let mut offset = 0;
-
-            while length < size {
-                if i == size_of::<Cell>() - 1 {
-                    cell = read(address.offset(offset));
-                    offset += 1;
-                }
+        let header = self.header();
 
-                ch = (cell >> (i * CHARBITS)) as u8;
+        if header.is_null() {
+            return Err(AmxError::Memory);
+        }
 
-                if ch == 0 {
-                    break;
-                }
+        let (defsize, (start, end)) = unsafe { ((*header).defsize as usize, table(&*header)) };
 
-                string.push(ch);
-                length += 1;
-                i = (i + size_of::<Cell>() - 1) % size_of::<Cell>();
-            }
-        } else {
-            let mut length = 0;
-            let mut byte = read(address.offset(length));
+        let known_size = defsize == size_of::<types::AMX_FUNCSTUB>() || defsize == size_of::<types::FUNCSTUBNT>();
 
-            while byte != 0 && length < size as isize {
-                string.push(byte as u8);
-                length += 1;
-                byte = read(address.offset(length));
-            }
+        if !known_size || start < 0 || start > end {
+            return Err(AmxError::Format);
         }
 
-         //from_vec_unchecked always adds nul at the end. Assuming AMX knows what it's doing, no need to check for internal nuls.
-        CString::from_vec_unchecked(string)
+        let count = (end - start) as usize / defsize;
+
+        if index < 0 || index as usize >= count {
+            return Err(AmxError::Index);
+        }
+
+        unsafe {
+            Ok((header as *mut u8).add(start as usize + index as usize * defsize))
+        }
     }
 
-    /// Gets a CString from AMX, given a cell with a string.
-    /// 
-    /// Since SA-MP does not support Unicode and relies on Windows codepages,
-    /// this function will work correctly for all servers, including the ones which use (or whose users use)
+    /// Reads a name of an entry of a header table, see `stub`.
+    fn stub_name(&self, table: fn(&types::AMX_HEADER) -> (i32, i32), index: i32) -> AmxResult<&CStr> {
+        let stub = self.stub(table, index)?;
+
+        let name = unsafe {
+            let header = self.header();
+
+            let str_ptr = if (*header).defsize as usize == size_of::<types::FUNCSTUBNT>() {
+                (header as *const u8).add((*(stub as *const types::FUNCSTUBNT)).nameofs as usize) as *const c_char
+            } else {
+                (*(stub as *const types::AMX_FUNCSTUB)).name.as_ptr() as *const c_char
+            };
+
+            CStr::from_ptr(str_ptr)
+        };
+
+        Ok(name)
+    }
+
+    /// Get an address of a native by its index.
+    ///
+    /// Returns `AmxError::Index` if there is no native with such index.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use] extern crate samp_sdk;
+    /// use samp_sdk::amx::AmxError;
+    /// use samp_sdk::testing::TestAmx;
+    /// use samp_sdk::types;
+    ///
+    /// extern "C" fn kick(_: *mut types::AMX, _: *mut types::Cell) -> types::Cell { 1 }
+    ///
+    /// # fn main() {
+    /// for mut test in vec![TestAmx::new(&[], 16), TestAmx::with_inline_names(&[], 16)] {
+    ///     test.add_native("SetPlayerHealth");
+    ///     test.add_native("Kick");
+    ///
+    ///     let amx = test.amx();
+    ///     let _ = amx.register(&natives!{ "Kick" => kick });
+    ///
+    ///     assert_eq!(amx.get_native_addr(0).unwrap(), 0);
+    ///     assert_eq!(amx.get_native_addr(1).unwrap(), kick as usize as types::Ucell as usize);
+    ///
+    ///     match amx.get_native_addr(2) {
+    ///         Err(AmxError::Index) => (),
+    ///         _ => panic!("there are only two natives"),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn get_native_addr(&self, index: i32) -> AmxResult<usize> {
+        let stub = self.stub(|header| (header.natives, header.libraries), index)?;
+
+        // Both kinds of stubs start with the address.
+        let amx_addr = unsafe { (*(stub as *const types::AMX_FUNCSTUB)).address };
+
+        Ok(amx_addr as usize)
+    }
+
+    /// Replaces a native registered in the AMX and returns the previous function.
+    ///
+    /// Lets a plugin hook a native of the server or another plugin and call through to the original one.
+    /// Returns `AmxError::NotFound` if the script doesn't use the native or it isn't registered yet.
+    ///
+    /// # Safety
+    /// Addresses of natives are stored in cells, so it only works with 32-bit pointers, as in the SA:MP server.
+    /// The native must be replaced before the script calls it (e.g. in `amx_load`), because the interpreter
+    /// may patch call sites with the address after the first call. The returned function must only be called
+    /// with `AMX` and `params` given to the hook, while the plugin which registered it is loaded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    /// use samp_sdk::types::{self, AmxNative, Cell};
+    ///
+    /// static mut ORIGINAL_KICK: Option<AmxNative> = None;
+    ///
+    /// extern "C" fn kick_hook(amx: *mut types::AMX, params: *mut Cell) -> Cell {
+    ///     // log the kick here, then call the original native
+    ///     match unsafe { ORIGINAL_KICK } {
+    ///         Some(kick) => kick(amx, params),
+    ///         None => 0,
+    ///     }
+    /// }
+    ///
+    /// fn hook_kick(amx: &AMX) -> AmxResult<()> {
+    ///     unsafe {
+    ///         ORIGINAL_KICK = Some(amx.replace_native("Kick", kick_hook)?);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// The address in the native table is swapped, the previous one is returned:
+    ///
+    /// ```
+    /// #[macro_use] extern crate samp_sdk;
+    /// use samp_sdk::amx::AmxError;
+    /// use samp_sdk::testing::TestAmx;
+    /// use samp_sdk::types::{self, Cell, Ucell};
+    ///
+    /// extern "C" fn kick(_: *mut types::AMX, _: *mut Cell) -> Cell { 1 }
+    /// extern "C" fn kick_hook(_: *mut types::AMX, _: *mut Cell) -> Cell { 2 }
+    ///
+    /// # fn main() {
+    /// let mut test = TestAmx::new(&[], 16);
+    /// test.add_native("Ban");
+    /// let index = test.add_native("Kick");
+    ///
+    /// let amx = test.amx();
+    /// let _ = amx.register(&natives!{ "Kick" => kick });
+    ///
+    /// let previous = unsafe { amx.replace_native("Kick", kick_hook) }.unwrap();
+    ///
+    /// // Addresses are kept in cells.
+    /// assert_eq!(previous as usize as Ucell, kick as usize as Ucell);
+    /// assert_eq!(amx.get_native_addr(index).unwrap(), kick_hook as usize as Ucell as usize);
+    ///
+    /// // `Ban` isn't registered.
+    /// match unsafe { amx.replace_native("Ban", kick_hook) } {
+    ///     Err(AmxError::NotFound) => (),
+    ///     _ => panic!("there is no function to replace"),
+    /// }
+    /// # }
+    /// ```
+    pub unsafe fn replace_native(&self, name: &str, native: types::AmxNative) -> AmxResult<types::AmxNative> {
+        let index = self.find_native(name)?;
+
+        // Both kinds of stubs start with the address.
+        let stub = self.stub(|header| (header.natives, header.libraries), index)? as *mut types::AMX_FUNCSTUB;
+        let previous = (*stub).address;
+
+        if previous == 0 {
+            return Err(AmxError::NotFound);
+        }
+
+        (*stub).address = native as usize as types::Ucell;
+
+        Ok(transmute::<usize, types::AmxNative>(previous as usize))
+    }
+
+    /// Returns the instruction pointer.
+    #[inline]
+    pub fn cip(&self) -> Cell {
+        unsafe { (*self.amx).cip }
+    }
+
+    /// Returns the stack frame pointer.
+    #[inline]
+    pub fn frm(&self) -> Cell {
+        unsafe { (*self.amx).frm }
+    }
+
+    /// Returns the stack pointer.
+    #[inline]
+    pub fn stk(&self) -> Cell {
+        unsafe { (*self.amx).stk }
+    }
+
+    /// Returns the top of the heap.
+    #[inline]
+    pub fn hea(&self) -> Cell {
+        unsafe { (*self.amx).hea }
+    }
+
+    /// Returns the top of the stack.
+    #[inline]
+    pub fn stp(&self) -> Cell {
+        unsafe { (*self.amx).stp }
+    }
+
+    /// Returns the primary register.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::testing::TestAmx;
+    ///
+    /// let test = TestAmx::new(&[], 16);
+    /// let amx = test.amx();
+    ///
+    /// amx.set_pri(10);
+    /// amx.set_alt(20);
+    ///
+    /// assert_eq!(amx.pri(), 10);
+    /// assert_eq!(amx.alt(), 20);
+    /// ```
+    #[inline]
+    pub fn pri(&self) -> Cell {
+        unsafe { (*self.amx).pri }
+    }
+
+    /// Sets the primary register.
+    ///
+    /// It's for advanced use, the script relies on registers between instructions.
+    #[inline]
+    pub fn set_pri(&self, value: Cell) {
+        unsafe { (*self.amx).pri = value }
+    }
+
+    /// Returns the alternate register.
+    #[inline]
+    pub fn alt(&self) -> Cell {
+        unsafe { (*self.amx).alt }
+    }
+
+    /// Sets the alternate register.
+    ///
+    /// It's for advanced use, the script relies on registers between instructions.
+    #[inline]
+    pub fn set_alt(&self, value: Cell) {
+        unsafe { (*self.amx).alt = value }
+    }
+
+    /// Returns how many bytes of the stack are used.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use] extern crate samp_sdk;
+    /// use samp_sdk::amx::AMX;
+    ///
+    /// fn log_frame(amx: &AMX) {
+    ///     log!("cip: {:#x}, frm: {:#x}, stack used: {} bytes", amx.cip(), amx.frm(), amx.stack_used());
+    /// }
+    /// ```
+    #[inline]
+    pub fn stack_used(&self) -> Cell {
+        self.stp() - self.stk()
+    }
+
+    /// Checks that the AMX has a valid header, returns `AmxError::Version` if it hasn't.
+    ///
+    /// Enabled by the `checked` feature to catch a stale or wrong AMX pointer during development,
+    /// does nothing without it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(feature = "checked")] {
+    /// use samp_sdk::testing::TestAmx;
+    /// # use samp_sdk::amx::AmxError;
+    /// # use samp_sdk::types;
+    ///
+    /// let test = TestAmx::new(&[0], 16);
+    /// let amx = test.amx();
+    ///
+    /// assert!(amx.check().is_ok());
+    /// #
+    /// # // A header without the AMX magic.
+    /// # unsafe { (*(amx.header() as *mut types::AMX_HEADER)).magic = 0; }
+    /// #
+    /// # match amx.get_address::<i32>(0) {
+    /// #     Err(AmxError::Version) => (),
+    /// #     _ => panic!("the header has no AMX magic"),
+    /// # }
+    /// # }
+    /// ```
+    #[cfg(feature = "checked")]
+    pub fn check(&self) -> AmxResult<()> {
+        if self.amx.is_null() {
+            return Err(AmxError::Version);
+        }
+
+        let header = self.header();
+
+        if header.is_null() || unsafe { (*header).magic } != AMX_MAGIC {
+            Err(AmxError::Version)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks that the AMX has a valid header, returns `AmxError::Version` if it hasn't.
+    ///
+    /// Does nothing without the `checked` feature.
+    #[cfg(not(feature = "checked"))]
+    #[inline(always)]
+    pub fn check(&self) -> AmxResult<()> {
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn header(&self) -> *const types::AMX_HEADER {
+        unsafe {
+            (*self.amx).base as _
+        }
+    }
+
+    /// Gets length of a string.
+    pub fn string_len(&self, address: *const Cell) -> AmxResult<usize> {
+        let string_len = import!(StrLen);
+        let mut length = 0;
+
+        call!(string_len(address, &mut length) => length as usize)
+    }
+
+    /// Gets a string from AMX.
+    ///
+    /// Deprecated due to its assumption of cp1251 encoding. Use AMX::get_cstring_of_length().
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use] extern crate samp_sdk;
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    /// use samp_sdk::types::Cell;
+    ///
+    /// pub struct MyPlugin;
+    ///
+    /// impl MyPlugin {
+    ///
+    ///     fn raw_function(&self, amx: &AMX, params: *mut Cell) -> AmxResult<Cell> {
+    ///         unsafe {
+    ///             let ptr = std::ptr::read(params.offset(1));
+    ///             let addr = amx.get_address::<i32>(ptr)?; // get a pointer from amx
+    ///             let len = amx.string_len(addr)?; // get string length in amx
+    ///             let string = amx.get_string_of_length(addr, len + 1)?; // convert amx string to rust CString
+    ///
+    ///             log!("got string: {}", string);
+    ///         }
+    ///
+    ///         Ok(0)
+    ///     }
+    ///
+    /// }
+    /// ```
+    #[deprecated(note = "Deprecated due to its assumption of cp1251 encoding. Use AMX::get_cstring_of_length().")]
+    pub unsafe fn get_string_of_length(&self, address: *const Cell, size: usize) -> AmxResult<String> {
+        let cstring = self.get_cstring_of_length(address,size);
+        crate::cp1251::decode(cstring.as_bytes())
+    }
+
+    /// Gets a string from a raw pointer to `Cell`.
+    ///
+    /// Deprecated due to its assumption of cp1251 encoding. Use AMX::get_cstring().
+    ///
+    /// # Examples
+    /// ```
+    /// use samp_sdk::log;
+    /// use samp_sdk::types::Cell;
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    ///
+    /// // native:PushString(const string[]);
+    /// fn raw_arguments(amx: &AMX, args: *mut Cell) -> AmxResult<Cell> {
+    ///     let string = amx.get_string( unsafe { args.offset(1) } )?;
+    ///     log!("got a string: {}", string);
+    ///     Ok(0)
+    /// }
+    /// ```
+    #[deprecated(note = "Deprecated due to its assumption of cp1251 encoding. Use AMX::get_cstring().")]
+    pub fn get_string(&self, cell: *mut Cell) -> AmxResult<String> {
+        let cstring = self.get_cstring(cell)?;
+        crate::cp1251::decode(cstring.as_bytes())
+    }
+
+    /// Gets a CString from AMX, given a cell address and length. Mostly for internal use.
+    /// You are better off using AMX::get_cstring() if you only have the Cell containing the string.
+    /// 
+    /// Since SA-MP does not support Unicode and relies on Windows codepages,
+    /// this function will work correctly for all servers, including the ones which use (or whose users use)
     /// codepages other than cp1251.
     /// 
     /// It is also faster, because it does not have to unnecessarily validate and convert SA-MP's strings to UTF8. 
@@ -623,91 +1708,1117 @@ impl AMX {
     /// # Examples
     ///
     /// ```
-    /// use samp_sdk::log;
-    /// use samp_sdk::cp1251;
-    /// use samp_sdk::types::Cell;
-    /// use samp_sdk::amx::{AMX, AmxResult};
-    ///
-    /// // native:PushString(const string[]);
-    /// fn raw_arguments(amx: &AMX, args: *mut Cell) -> AmxResult<Cell> {
-    ///     let passed_string_argument = unsafe { args.offset(1) };
-    ///     let string = amx.get_cstring( passed_string_argument )?;
-    ///     
-    ///     log!("got CString: {:?}, converted to UTF-8 Rust String: {:?}", string, cp1251::decode(string.to_bytes()));
-    ///     Ok(0)
+    /// #[macro_use] extern crate samp_sdk;
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    /// use samp_sdk::types::Cell;
+    /// use samp_sdk::cp1251;
+    ///
+    /// pub struct MyPlugin;
+    ///
+    /// impl MyPlugin {
+    ///
+    ///     fn raw_function(&self, amx: &AMX, params: *mut Cell) -> AmxResult<Cell> {
+    ///         unsafe {
+    ///             let ptr = std::ptr::read(params.offset(1));
+    ///             let addr = amx.get_address::<i32>(ptr)?; // get a pointer from amx
+    ///             let len = amx.string_len(addr)?; // get string length in amx
+    ///             let string = unsafe { 
+    ///                 amx.get_cstring_of_length(addr, len + 1) 
+    ///             };
+    ///
+    ///             log!("got CString: {:?}, converted to UTF-8 Rust String: {:?}", string, cp1251::decode(string.to_bytes()));
+    ///         }
+    ///
+    ///         Ok(0)
+    ///     }
+    ///
+    /// }
+    /// ```
+    ///
+    /// Reading stops at the null character or at the end of AMX memory, even if `size` is larger:
+    ///
+    /// ```
+    /// use samp_sdk::testing::{self, TestAmx};
+    ///
+    /// let test = TestAmx::new(&testing::string("abcd"), 16);
+    /// let amx = test.amx();
+    ///
+    /// let string = unsafe { amx.get_cstring_of_length(test.memory().as_ptr(), 100) };
+    /// assert_eq!(string.to_bytes(), b"abcd");
+    /// #
+    /// # // No null character till the end of the data section.
+    /// # let test = TestAmx::new(&[0x61, 0x62, 0x63, 0x64], 16);
+    /// # let string = unsafe { test.amx().get_cstring_of_length(test.memory().as_ptr(), 100) };
+    /// # assert_eq!(string.to_bytes(), b"abcd");
+    /// ```
+    pub unsafe fn get_cstring_of_length(&self, address: *const Cell, size: usize) -> CString {
+        let mut string = Vec::with_capacity(size);
+        self.read_string(address, size, &mut string);
+
+         //from_vec_unchecked always adds nul at the end. Assuming AMX knows what it's doing, no need to check for internal nuls.
+        CString::from_vec_unchecked(string)
+    }
+
+    /// Reads bytes of a packed or unpacked string to the end of `string`.
+    ///
+    /// Stops at the end of the data and heap or the end of the stack, whichever `address` is in.
+    unsafe fn read_string(&self, address: *const Cell, size: usize, string: &mut Vec<u8>) {
+        let available = self.cells_until_end(address);
+
+        if available == 0 {
+            return;
+        }
+
+        if packed::is_packed(read(address)) {
+            // packed string
+            let cells = ::std::slice::from_raw_parts(address, size.div_ceil(size_of::<Cell>()).min(available));
+            let start = string.len();
+
+            packed::unpack_into(cells, string);
+            string.truncate(start + size);
+        } else {
+            let cells = ::std::slice::from_raw_parts(address, size.min(available));
+            let characters = cells.iter()
+                .take_while(|&&cell| cell != 0)
+                .map(|&cell| cell as u8);
+
+            string.extend(characters);
+        }
+    }
+
+    /// Returns how many cells can be read from a physical address until the end of its memory area.
+    ///
+    /// Returns 0 if the address is outside of the data section and stack.
+    fn cells_until_end(&self, address: *const Cell) -> usize {
+        if self.amx.is_null() {
+            return 0;
+        }
+
+        unsafe {
+            let data = if (*self.amx).data.is_null() {
+                let header = self.header();
+
+                if header.is_null() {
+                    return 0;
+                }
+
+                (*self.amx).base as usize + (*header).dat as usize
+            } else {
+                (*self.amx).data as usize
+            };
+
+            let offset = (address as usize).wrapping_sub(data);
+
+            let end = if offset < (*self.amx).hea as usize {
+                (*self.amx).hea as usize
+            } else if offset >= (*self.amx).stk as usize && offset < (*self.amx).stp as usize {
+                (*self.amx).stp as usize
+            } else {
+                return 0;
+            };
+
+            (end - offset) / size_of::<Cell>()
+        }
+    }
+
+    /// Gets a CString from AMX, given a cell with a string.
+    /// 
+    /// Since SA-MP does not support Unicode and relies on Windows codepages,
+    /// this function will work correctly for all servers, including the ones which use (or whose users use)
+    /// codepages other than cp1251.
+    /// 
+    /// It is also faster, because it does not have to unnecessarily validate and convert SA-MP's strings to UTF8. 
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::log;
+    /// use samp_sdk::cp1251;
+    /// use samp_sdk::types::Cell;
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    ///
+    /// // native:PushString(const string[]);
+    /// fn raw_arguments(amx: &AMX, args: *mut Cell) -> AmxResult<Cell> {
+    ///     let passed_string_argument = unsafe { args.offset(1) };
+    ///     let string = amx.get_cstring( passed_string_argument )?;
+    ///     
+    ///     log!("got CString: {:?}, converted to UTF-8 Rust String: {:?}", string, cp1251::decode(string.to_bytes()));
+    ///     Ok(0)
+    /// }
+    /// ```
+    #[inline]
+    pub fn get_cstring(&self, cell: *mut Cell) -> AmxResult<CString> {
+        let pointer = unsafe {
+            ::std::ptr::read(cell)
+        };
+
+        let address = self.get_address::<i32>(pointer)?;
+        let len = self.string_len(address)?;
+        let cstr = unsafe { 
+            self.get_cstring_of_length(address, len)
+        };
+
+        Ok(cstr)
+    }
+
+    /// Checks if a string at the address is packed.
+    ///
+    /// It only looks at the string's current contents: an empty string is considered unpacked
+    /// and there is no way to know which form the script expects for a buffer it's going to fill.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    /// use samp_sdk::types::Cell;
+    ///
+    /// // native: Echo(const text[]);
+    /// fn echo(amx: &AMX, args: *mut Cell) -> AmxResult<Cell> {
+    ///     let text = unsafe { args.offset(1) };
+    ///     let packed = amx.detect_packed(unsafe { *text })?;
+    ///     let string = amx.get_cstring(text)?;
+    ///     let index = amx.find_public("OnEcho")?;
+    ///
+    ///     amx.call_public_index(index)
+    ///         .arg_string_as(&string, packed)
+    ///         .exec()
+    /// }
+    /// ```
+    pub fn detect_packed(&self, address: Cell) -> AmxResult<bool> {
+        let first: &mut Cell = self.get_address(address)?;
+        Ok(packed::is_packed(*first))
+    }
+
+    /// Gets bytes of a string from AMX into a buffer, given a cell with a string.
+    ///
+    /// Works like `get_cstring`, but the buffer is cleared and reused, so reading
+    /// a lot of strings doesn't allocate memory for each of them.
+    /// The buffer doesn't get a null terminator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use] extern crate samp_sdk;
+    /// use samp_sdk::types::Cell;
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    ///
+    /// // native:CountSpaces(const ...);
+    /// fn count_spaces(amx: &AMX, params: *mut Cell) -> AmxResult<Cell> {
+    ///     let mut buffer = Vec::with_capacity(128);
+    ///     let mut spaces = 0;
+    ///
+    ///     for index in 1..=args_count!(params) {
+    ///         amx.get_string_into(unsafe { params.add(index) }, &mut buffer)?;
+    ///         spaces += buffer.iter().filter(|&&byte| byte == b' ').count();
+    ///     }
+    ///
+    ///     Ok(spaces as Cell)
+    /// }
+    /// ```
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    pub fn get_string_into(&self, cell: *mut Cell, buffer: &mut Vec<u8>) -> AmxResult<()> {
+        let pointer = unsafe {
+            ::std::ptr::read(cell)
+        };
+
+        let address = self.get_address::<i32>(pointer)?;
+        let len = self.string_len(address)?;
+
+        buffer.clear();
+
+        unsafe {
+            self.read_string(address, len, buffer);
+        }
+
+        Ok(())
+    }
+
+    /// Gets a string from AMX into a buffer and decodes it from cp1251.
+    ///
+    /// ASCII strings are borrowed from the buffer, so reading them with a reused buffer doesn't allocate.
+    /// See `get_string_into` and `cp1251::decode_cow`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::types::Cell;
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    ///
+    /// // native:IsHelpCommand(const command[]);
+    /// fn is_help_command(amx: &AMX, args: *mut Cell) -> AmxResult<Cell> {
+    ///     let mut buffer = Vec::new();
+    ///     let command = amx.get_string_cow(unsafe { args.offset(1) }, &mut buffer)?;
+    ///
+    ///     Ok((command == "/help") as Cell)
+    /// }
+    /// ```
+    pub fn get_string_cow<'b>(&self, cell: *mut Cell, buffer: &'b mut Vec<u8>) -> AmxResult<Cow<'b, str>> {
+        self.get_string_into(cell, buffer)?;
+        crate::cp1251::decode_cow(buffer)
+    }
+
+    /// Gets a string from AMX, given a cell with a string, and decodes it as UTF-8.
+    ///
+    /// Servers that keep UTF-8 in Pawn strings can use it instead of `get_cstring` and `cp1251::decode`.
+    /// The string is validated with `amx_UTF8Check` and decoded character by character with `amx_UTF8Get`,
+    /// if it isn't valid UTF-8 it is decoded from cp1251.
+    /// Returns `AmxError::Utf8` if the server accepts a string which Rust doesn't consider valid UTF-8,
+    /// e.g. an encoded surrogate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::log;
+    /// use samp_sdk::types::Cell;
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    ///
+    /// // native:PrintMessage(const message[]);
+    /// fn print_message(amx: &AMX, args: *mut Cell) -> AmxResult<Cell> {
+    ///     let message = amx.get_string_utf8(unsafe { args.offset(1) })?;
+    ///     log!("message: {}", message);
+    ///     Ok(1)
+    /// }
+    /// ```
+    ///
+    /// A string which isn't valid UTF-8 is taken as cp1251:
+    ///
+    /// ```
+    /// use samp_sdk::testing::TestAmx;
+    ///
+    /// // "Привет" in cp1251 at the address 0.
+    /// let test = TestAmx::new(&[0xCF, 0xF0, 0xE8, 0xE2, 0xE5, 0xF2, 0], 16);
+    /// let mut arg = 0;
+    ///
+    /// assert_eq!(test.amx().get_string_utf8(&mut arg).unwrap(), "Привет");
+    /// ```
+    pub fn get_string_utf8(&self, cell: *mut Cell) -> AmxResult<String> {
+        let utf8_check = import!(UTF8Check);
+        let utf8_get = import!(UTF8Get);
+
+        let cstring = self.get_cstring(cell)?;
+        let mut length = 0;
+
+        let result = utf8_check(cstring.as_ptr(), &mut length);
+
+        match ret!(result, ()) {
+            Ok(()) => (),
+            Err(AmxError::Format) => return crate::cp1251::decode(cstring.as_bytes()),
+            Err(err) => return Err(err),
+        }
+
+        let bytes = cstring.as_bytes();
+        let mut string = String::with_capacity(bytes.len());
+        let mut position = cstring.as_ptr();
+        let end = unsafe { position.add(bytes.len()) };
+
+        while position < end {
+            let mut next = position;
+            let mut value = 0;
+
+            call!(utf8_get(position, &mut next, &mut value) => ())?;
+
+            if next <= position {
+                return Err(AmxError::Utf8);
+            }
+
+            string.push(char::from_u32(value as u32).ok_or(AmxError::Utf8)?);
+            position = next;
+        }
+
+        Ok(string)
+    }
+
+    /// Allots memory for a UTF-8 string and pushes it to the AMX stack.
+    ///
+    /// Characters are encoded with `amx_UTF8Put`, so the script gets UTF-8 instead of cp1251.
+    /// Returns `AmxError::Format` if the string contains a null character.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::testing::TestAmx;
+    ///
+    /// let test = TestAmx::new(&[], 64);
+    /// let amx = test.amx();
+    ///
+    /// let mut address = amx.push_string_utf8("Привет ✓", false).unwrap();
+    /// assert_eq!(amx.get_string_utf8(&mut address).unwrap(), "Привет ✓");
+    /// # let mut address = amx.push_string_utf8("Привет ✓", true).unwrap();
+    /// # assert_eq!(amx.get_string_utf8(&mut address).unwrap(), "Привет ✓");
+    /// # assert!(amx.push_string_utf8("a\0b", false).is_err());
+    /// ```
+    pub fn push_string_utf8(&self, string: &str, packed: bool) -> AmxResult<Cell> {
+        let utf8_put = import!(UTF8Put);
+
+        if string.contains('\0') {
+            return Err(AmxError::Format);
+        }
+
+        let mut bytes = vec![0u8; string.len() + 1];
+        let start = bytes.as_mut_ptr() as *mut c_char;
+        let mut position = start;
+
+        for character in string.chars() {
+            let left = string.len() - (position as usize - start as usize);
+            let mut next = position;
+
+            call!(utf8_put(position, &mut next, left as i32, character as Cell) => ())?;
+            position = next;
+        }
+
+        bytes.truncate(position as usize - start as usize);
+
+        // The string has no null characters and they are never encoded.
+        let cstring = CString::new(bytes).map_err(|_| AmxError::Format)?;
+        self.push_string(&cstring, packed)
+    }
+
+    /// Writes a string to the AMX memory address given via dest_address.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use] extern crate samp_sdk;
+    /// use samp_sdk::types::Cell;
+    /// use std::ffi::{CStr,CString};
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    ///
+    /// // native: rot13(const source[], dest[], size=sizeof(dest));
+    /// // define_native!(n_rot13, source: CString, dest_ptr: &mut types::Cell, size: usize);
+    ///
+    /// fn n_rot13(amx: &AMX, source: CString, dest_ptr: &mut Cell, size: usize) -> AmxResult<Cell> {
+    ///     let roted = rot13(&source);
+    ///     unsafe { 
+    ///         amx.set_cstr_of_size(&roted, dest_ptr, size - 1); // leave a cell for the null terminator
+    ///     }
+    ///     Ok(0)
+    /// }
+    ///
+    /// fn rot13(string: &CStr) -> CString {
+    ///      let alphabet: [u8; 26] = [
+    ///          b'a', b'b', b'c', b'd', b'e', b'f', b'g', b'h', b'i', b'j', b'k', b'l', b'm',
+    ///          b'n', b'o', b'p', b'q', b'r', b's', b't', b'u', b'v', b'w', b'x', b'y', b'z'
+    ///      ];
+    ///
+    ///      CString::new::<Vec<u8>>(
+    ///         string.to_bytes().into_iter()
+    ///            .map(|c| *alphabet.iter()
+    ///                              .chain(alphabet.iter())
+    ///                              .skip_while(|&x| *x != *c)
+    ///                              .nth(13)
+    ///                              .unwrap_or(&c))
+    ///            .collect()
+    ///      ).unwrap()
+    /// }
+    /// ```
+    pub unsafe fn set_cstr_of_size(&self, string: &CStr, dest_address: *mut Cell, allowed_length: usize) {
+        let bytes = string.to_bytes();
+
+        // The following is the idiomatic way of doing this in Rust, as per Clippy. 
+        // All of it gets optimized away by the compiler.
+        for (position, byte) in bytes.iter().enumerate().take(allowed_length) {
+            *(dest_address.add(position)) = i32::from(*byte);
+        }
+
+        *(dest_address.add( std::cmp::min(allowed_length,bytes.len()) )) = 0;
+    }
+
+    /// Writes a string encoded to cp1251 into an AMX array of given size.
+    ///
+    /// The string is cut to fit the array with the null terminator, so at most `size` cells are written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    /// use samp_sdk::types::Cell;
+    ///
+    /// // native: GetVersionString(dest[], size = sizeof(dest));
+    /// // define_native!(get_version_string, dest: Cell, size: usize);
+    /// fn get_version_string(amx: &AMX, dest: Cell, size: usize) -> AmxResult<Cell> {
+    ///     amx.write_string(dest, "0.8.4", size)?;
+    ///     Ok(1)
+    /// }
+    /// ```
+    ///
+    /// The whole destination must be in the data section and the heap or in the stack:
+    ///
+    /// ```
+    /// use samp_sdk::amx::AmxError;
+    /// use samp_sdk::testing::TestAmx;
+    ///
+    /// // A `dest[4]` array at the address 0, then the empty heap and 64 cells of the stack.
+    /// let test = TestAmx::new(&[0; 4], 64);
+    /// let amx = test.amx();
+    ///
+    /// amx.write_string(0, "0.8.4", 4).unwrap();
+    /// assert_eq!(test.memory()[..4], [48, 46, 56, 0]);
+    ///
+    /// // A wrong size makes it run past the array.
+    /// match amx.write_string(0, "0.8.4", 6) {
+    ///     Err(AmxError::MemoryAccess) => (),
+    ///     _ => panic!("the array has only 4 cells"),
+    /// }
+    ///
+    /// // From the data section across the gap up to the pushed value on the stack.
+    /// amx.push(1).unwrap();
+    ///
+    /// match amx.write_string(0, &"a".repeat(67), 68) {
+    ///     Err(AmxError::MemoryAccess) => (),
+    ///     _ => panic!("the string crosses the gap between the heap and the stack"),
+    /// }
+    /// ```
+    pub fn write_string(&self, address: Cell, string: &str, size: usize) -> AmxResult<()> {
+        if size == 0 {
+            return Ok(());
+        }
+
+        let bytes = crate::cp1251::encode(string)?;
+        let length = std::cmp::min(bytes.len(), size - 1);
+
+        let dest = self.range_address(address, length + 1)?;
+
+        unsafe {
+            for (position, byte) in bytes.iter().take(length).enumerate() {
+                *dest.add(position) = Cell::from(*byte);
+            }
+
+            *dest.add(length) = 0;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a string to AMX memory using the `amx_SetString` function of the server.
+    ///
+    /// Unlike `set_cstr_of_size` it can write packed strings.
+    /// `size` is the size of the destination buffer in characters, including the null terminator.
+    ///
+    /// # Safety
+    /// `dest_address` must point to AMX memory large enough to hold `size` characters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    /// use samp_sdk::types::Cell;
+    /// use std::ffi::CString;
+    ///
+    /// // native: GetPackedName(dest[], size = sizeof(dest));
+    /// fn get_packed_name(amx: &AMX, dest: &mut Cell, size: usize) -> AmxResult<Cell> {
+    ///     let name = CString::new("Name_Surname").unwrap();
+    ///     unsafe {
+    ///         amx.set_string(dest, &name, true, size)?;
+    ///     }
+    ///     Ok(1)
+    /// }
+    /// ```
+    ///
+    /// Both forms are read back with `get_string`:
+    ///
+    /// ```
+    /// use samp_sdk::testing::TestAmx;
+    /// use std::ffi::CString;
+    ///
+    /// // A `dest[16]` array at the address 0.
+    /// let test = TestAmx::new(&[0; 16], 16);
+    /// let amx = test.amx();
+    /// let name = CString::new("Name_Surname").unwrap();
+    /// let mut address = 0;
+    ///
+    /// unsafe {
+    ///     amx.set_string(amx.get_address(0).unwrap(), &name, true, 16).unwrap();
+    /// }
+    ///
+    /// assert_eq!(amx.get_string(&mut address).unwrap(), "Name_Surname");
+    ///
+    /// unsafe {
+    ///     amx.set_string(amx.get_address(0).unwrap(), &name, false, 16).unwrap();
+    /// }
+    ///
+    /// assert_eq!(amx.get_string(&mut address).unwrap(), "Name_Surname");
+    /// # unsafe { amx.set_string(amx.get_address(0).unwrap(), &name, false, 5).unwrap(); }
+    /// # assert_eq!(amx.get_string(&mut address).unwrap(), "Name");
+    /// # assert_eq!(test.memory()[4], 0);
+    /// ```
+    pub unsafe fn set_string(&self, dest_address: *mut Cell, string: &CStr, packed: bool, size: usize) -> AmxResult<()> {
+        let set_string = import!(SetString);
+        call!(set_string(dest_address, string.as_ptr(), packed as i32, 0, size) => ())
+    }
+
+    /// Sets a function which the server calls on every `break` instruction of the script.
+    ///
+    /// The compiler emits `break` before each statement when a script is compiled with debug information (`-d2`, `-d3`),
+    /// so a hook can trace lines or count executed statements. The hook is called very often and makes scripts
+    /// noticeably slower, set it only while profiling. A non-zero value returned by the hook aborts the script with that error.
+    pub fn set_debug_hook(&self, hook: types::AmxDebug) -> AmxResult<()> {
+        let set_debug_hook = import!(SetDebugHook);
+        call!(set_debug_hook(self.amx, hook) => ())
+    }
+
+    /// Sets a closure as a debug hook, see `set_debug_hook`.
+    ///
+    /// The closure replaces a previously set one and is dropped when the AMX is unloaded.
+    /// Returning an error aborts the script. A panic is logged and aborts it with `AmxError::General`.
+    /// The closure may set another one, which takes its place after the call.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::amx::{AMX, AmxResult};
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// fn count_statements(amx: &AMX, counter: Arc<AtomicUsize>) -> AmxResult<()> {
+    ///     amx.set_debug_closure(move |_| {
+    ///         counter.fetch_add(1, Ordering::Relaxed);
+    ///         Ok(())
+    ///     })
+    /// }
+    /// ```
+    ///
+    /// `TestAmx` calls the hook once before each public:
+    ///
+    /// ```
+    /// use samp_sdk::amx::AmxError;
+    /// use samp_sdk::testing::TestAmx;
+    /// use samp_sdk::types;
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// let mut test = TestAmx::new(&[], 16);
+    /// let index = test.add_public("OnPlayerUpdate", |_, _| 1);
+    /// let amx = test.amx();
+    ///
+    /// let counter = Arc::new(AtomicUsize::new(0));
+    /// let calls = counter.clone();
+    ///
+    /// amx.set_debug_closure(move |_| {
+    ///     calls.fetch_add(1, Ordering::Relaxed);
+    ///     Ok(())
+    /// }).unwrap();
+    ///
+    /// // The hook is given to the AMX.
+    /// let hook = unsafe { std::ptr::read_unaligned(std::ptr::addr_of!((*amx.amx).debug) as *const Option<types::AmxDebug>) };
+    /// assert!(hook.is_some());
+    ///
+    /// assert_eq!(amx.exec(index).unwrap(), 1);
+    /// assert_eq!(amx.exec(index).unwrap(), 1);
+    /// assert_eq!(counter.load(Ordering::Relaxed), 2);
+    ///
+    /// // A hook that replaces itself and then panics.
+    /// amx.set_debug_closure(|amx| {
+    ///     amx.set_debug_closure(|_| Ok(())).unwrap();
+    ///     panic!("a bug in the hook");
+    /// }).unwrap();
+    ///
+    /// match amx.exec(index) {
+    ///     Err(err) => assert!(matches!(err.kind(), AmxError::General)),
+    ///     Ok(_) => panic!("the hook has panicked"),
+    /// }
+    ///
+    /// assert_eq!(amx.exec(index).unwrap(), 1);
+    /// ```
+    pub fn set_debug_closure<F: FnMut(&AMX) -> AmxResult<()> + Send + 'static>(&self, hook: F) -> AmxResult<()> {
+        debug_hooks.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(self.amx as usize, Box::new(hook));
+
+        self.set_debug_hook(call_debug_closure)
+    }
+
+    /// Raises an AMX error.
+    pub fn raise_error(&self, error: AmxError) -> AmxResult<()> {
+        let raise_error = import!(RaiseError);
+        call!(raise_error(self.amx, error.code()) => ())
+    }
+}
+
+/// Shows a summary of the AMX header: magic, versions, flags and sizes of sections in bytes.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::testing::TestAmx;
+/// # use samp_sdk::amx::AMX;
+/// # use samp_sdk::types;
+///
+/// // 4 cells of data, 64 cells of the heap and the stack.
+/// let test = TestAmx::new(&[0; 4], 64);
+/// let output = format!("{:?}", test.amx());
+///
+/// assert!(output.contains("magic: 0xf1e0"));
+/// assert!(output.contains("data_size: 16"));
+/// assert!(output.contains("stack_heap_size: 256"));
+/// #
+/// # // A corrupt header is shown as it is.
+/// # let header = test.amx().header() as *mut types::AMX_HEADER;
+/// # unsafe {
+/// #     (*header).cod = i32::MIN;
+/// #     (*header).dat = i32::MAX;
+/// # }
+/// #
+/// # let output = format!("{:?}", test.amx());
+/// # assert!(output.contains("code_size: -1"));
+/// #
+/// # assert_eq!(format!("{:?}", AMX::new(std::ptr::null_mut())), "AMX { amx: 0x0 }");
+/// ```
+impl fmt::Debug for AMX {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut debug = f.debug_struct("AMX");
+        debug.field("amx", &self.amx);
+
+        if !self.amx.is_null() && !self.header().is_null() {
+            let header = unsafe { *self.header() };
+            let (magic, flags) = (header.magic, header.flags);
+
+            debug
+                .field("magic", &format_args!("{:#x}", magic))
+                .field("file_version", &header.file_version)
+                .field("amx_version", &header.amx_version)
+                .field("flags", &format_args!("{:#x}", flags))
+                // A corrupt header mustn't make it panic.
+                .field("code_size", &header.dat.wrapping_sub(header.cod))
+                .field("data_size", &header.hea.wrapping_sub(header.dat))
+                .field("stack_heap_size", &header.stp.wrapping_sub(header.hea));
+        }
+
+        debug.finish()
+    }
+}
+
+/// An identifier of a loaded AMX.
+///
+/// Unlike `AMX` it can be stored and used later, e.g. in a timer, to get the AMX back with `amx::get`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AmxIdent(usize);
+
+impl AmxIdent {
+    /// Makes an identifier from a raw `types::AMX` pointer.
+    pub fn from_ptr(amx: *mut types::AMX) -> AmxIdent {
+        AmxIdent(amx as usize)
+    }
+}
+
+impl From<&AMX> for AmxIdent {
+    fn from(amx: &AMX) -> AmxIdent {
+        AmxIdent::from_ptr(amx.amx)
+    }
+}
+
+/// Returns a loaded AMX by its identifier.
+///
+/// Returns `None` if the AMX has been unloaded since.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::amx::{self, AMX, AmxIdent};
+/// use samp_sdk::types;
+///
+/// let ptr = 0x1000 as *mut types::AMX;
+/// let ident = AmxIdent::from(&AMX::new(ptr));
+///
+/// amx::add(ptr);
+/// assert_eq!(amx::get(ident).map(|amx| amx.amx), Some(ptr));
+///
+/// amx::remove(ptr);
+/// assert!(amx::get(ident).is_none());
+/// ```
+pub fn get(ident: AmxIdent) -> Option<AMX> {
+    if amx_list.lock().unwrap().contains(&ident.0) {
+        Some(AMX::new(ident.0 as *mut types::AMX))
+    } else {
+        None
+    }
+}
+
+/// Returns all loaded AMX instances.
+///
+/// The list is copied when it's called, so AMX instances loaded or unloaded
+/// during the iteration don't affect it.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::amx;
+/// use samp_sdk::types;
+///
+/// let first = 0x1000 as *mut types::AMX;
+/// let second = 0x2000 as *mut types::AMX;
+///
+/// amx::add(first);
+/// amx::add(second);
+///
+/// let mut loaded: Vec<_> = amx::all().map(|amx| amx.amx).collect();
+/// loaded.sort();
+///
+/// assert_eq!(loaded, vec![first, second]);
+/// ```
+pub fn all() -> impl Iterator<Item = AMX> {
+    let list: Vec<usize> = amx_list.lock().unwrap().iter().cloned().collect();
+    list.into_iter().map(|amx| AMX::new(amx as *mut types::AMX))
+}
+
+/// Returns summary code size, data size and stack/heap size of all loaded AMX instances.
+///
+/// See `AMX::mem_info`.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use] extern crate samp_sdk;
+/// use samp_sdk::amx;
+///
+/// fn log_memory_usage() {
+///     if let Ok((code, data, stack_heap)) = amx::total_mem_info() {
+///         log!("code: {}, data: {}, stack/heap: {}", code, data, stack_heap);
+///     }
+/// }
+/// ```
+pub fn total_mem_info() -> AmxResult<(i64, i64, i64)> {
+    all().try_fold((0, 0, 0), |(code, data, stack_heap), amx| {
+        let (amx_code, amx_data, amx_stack_heap) = amx.mem_info()?;
+        Ok((code + amx_code, data + amx_data, stack_heap + amx_stack_heap))
+    })
+}
+
+/// Calls a closure set by `AMX::set_debug_closure`.
+///
+/// The closure is taken out of the map while it runs, so it can set a new hook or exec another AMX.
+extern "C" fn call_debug_closure(amx: *mut types::AMX) -> i32 {
+    let taken = debug_hooks.lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .remove(&(amx as usize));
+
+    let mut hook = match taken {
+        Some(hook) => hook,
+        None => return AMX_ERR_NONE,
+    };
+
+    let result = crate::macros::catch_panic("Debug hook", Err(AmxError::General), || hook(&AMX::new(amx)));
+
+    // A hook set by the closure itself takes the place of the old one.
+    debug_hooks.lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(amx as usize)
+        .or_insert(hook);
+
+    match result {
+        Ok(()) => AMX_ERR_NONE,
+        Err(err) => err.code(),
+    }
+}
+
+/// Limits how many cells `AMX::allot` can ask for at once, `None` removes the limit.
+///
+/// Protects the AMX heap from sizes computed from bad script arguments.
+/// It's applied to all AMX instances and everything that allots memory: `push_array`, `push_string` and others.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::amx::{self, AMX, AmxError};
+///
+/// amx::set_allot_limit(Some(1024));
+///
+/// let amx = AMX::new(std::ptr::null_mut());
+///
+/// match amx.allot(4096) {
+///     Err(AmxError::Memory) => (),
+///     _ => panic!("4096 cells are above the limit"),
+/// }
+///
+/// // Passes the limit and fails only because the plugin isn't loaded.
+/// match amx.allot(16) {
+///     Err(AmxError::NullExport) => (),
+///     _ => panic!("16 cells are below the limit"),
+/// }
+/// ```
+pub fn set_allot_limit(cells: Option<usize>) {
+    allot_limit.store(cells.unwrap_or(usize::MAX), Ordering::Relaxed);
+}
+
+/// Adds an AMX to the list of loaded AMX instances.
+///
+/// Called from `AmxLoad` of `new_plugin!`.
+#[doc(hidden)]
+pub fn add(amx: *mut types::AMX) {
+    amx_list.lock().unwrap().insert(amx as usize);
+}
+
+/// Removes an AMX from the list of loaded AMX instances.
+///
+/// Called from `AmxUnload` of `new_plugin!`.
+#[doc(hidden)]
+pub fn remove(amx: *mut types::AMX) {
+    amx_list.lock().unwrap().remove(&(amx as usize));
+    debug_hooks.lock().unwrap_or_else(PoisonError::into_inner).remove(&(amx as usize));
+}
+
+/// A list of natives made by `natives!` macro.
+///
+/// Dereferences to a slice of `AMX_NATIVE_INFO`, which can be passed to `AMX::register`.
+/// Names are owned by the list and are valid until it's dropped, unless they are static.
+pub struct Natives {
+    names: Vec<Cow<'static, CStr>>,
+    natives: Vec<types::AMX_NATIVE_INFO>,
+}
+
+impl Natives {
+    pub fn new(natives: Vec<(Cow<'static, CStr>, types::AmxNative)>) -> Natives {
+        let infos = natives.iter()
+            .map(|(name, func)| types::AMX_NATIVE_INFO {
+                name: name.as_ptr(),
+                func: *func,
+            })
+            .collect();
+
+        Natives {
+            names: natives.into_iter().map(|(name, _)| name).collect(),
+            natives: infos,
+        }
+    }
+}
+
+impl Deref for Natives {
+    type Target = [types::AMX_NATIVE_INFO];
+
+    fn deref(&self) -> &Self::Target {
+        &self.natives
+    }
+}
+
+/// An argument stored by `PublicCall` or `DeferredCalls` until the public is executed.
+pub enum Argument {
+    Cell(Cell),
+    String(CString),
+    PackedString(CString),
+    Array(Vec<Cell>),
+}
+
+/// Builder of a public call made by `AMX::call_public`.
+pub struct PublicCall<'a> {
+    amx: &'a AMX,
+    index: AmxResult<i32>,
+    args: Vec<Argument>,
+    outs: Vec<(usize, &'a mut [Cell])>,
+}
+
+impl<'a> PublicCall<'a> {
+    /// Adds a primitive value, such as `i32` or `f32`.
+    ///
+    /// The value must have the size of a cell, otherwise `exec` returns `AmxError::Format`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::amx::AmxError;
+    /// use samp_sdk::testing::TestAmx;
+    ///
+    /// let mut test = TestAmx::new(&[], 64);
+    /// test.add_public("OnPlayerHit", |_, args| {
+    ///     assert_eq!(args, &[7, 25.5f32.to_bits() as i32]);
+    ///     1
+    /// });
+    ///
+    /// let amx = test.amx();
+    /// let hit = amx.call_public("OnPlayerHit").arg(7).arg(25.5f32).exec();
+    /// assert_eq!(hit.unwrap(), 1);
+    ///
+    /// match amx.call_public("OnPlayerHit").arg(7).arg(0x1_0000_0005i64).exec() {
+    ///     Err(AmxError::Format) => (),
+    ///     _ => panic!("i64 doesn't fit a cell"),
     /// }
+    ///
+    /// assert!(amx.call_public("OnPlayerHit").arg(true).exec().is_err());
     /// ```
-    #[inline]
-    pub fn get_cstring(&self, cell: *mut Cell) -> AmxResult<CString> {
-        let pointer = unsafe {
-            ::std::ptr::read(cell)
-        };
+    pub fn arg<T: Sized>(mut self, value: T) -> Self {
+        if size_of::<T>() != size_of::<Cell>() {
+            self.index = Err(AmxError::Format);
+            return self;
+        }
 
-        let address = self.get_address::<i32>(pointer)?;
-        let len = self.string_len(address)?;
-        let cstr = unsafe { 
-            self.get_cstring_of_length(address, len)
-        };
+        self.args.push(Argument::Cell(unsafe { transmute_copy(&value) }));
+        self
+    }
 
-        //std::mem::forget(addr);
+    /// Adds an argument of any kind.
+    pub fn argument(mut self, arg: Argument) -> Self {
+        self.args.push(arg);
+        self
+    }
 
-        Ok(cstr)
+    /// Adds a string, it is passed to the public as an unpacked string.
+    pub fn arg_string(mut self, string: &CStr) -> Self {
+        self.args.push(Argument::String(string.to_owned()));
+        self
     }
 
-    /// Writes a string to the AMX memory address given via dest_address.
+    /// Adds a string, which is passed as a packed string if `packed` is true.
+    ///
+    /// See `AMX::detect_packed`.
+    pub fn arg_string_as(mut self, string: &CStr, packed: bool) -> Self {
+        if packed {
+            self.args.push(Argument::PackedString(string.to_owned()));
+        } else {
+            self.args.push(Argument::String(string.to_owned()));
+        }
+
+        self
+    }
+
+    /// Adds an array.
+    ///
+    /// Elements must have the size of a cell, otherwise `exec` returns `AmxError::Format`.
+    pub fn arg_array<T: Sized>(mut self, array: &[T]) -> Self {
+        if size_of::<T>() != size_of::<Cell>() {
+            self.index = Err(AmxError::Format);
+            return self;
+        }
+
+        let cells = array.iter()
+            .map(|value| unsafe { transmute_copy(value) })
+            .collect();
+
+        self.args.push(Argument::Array(cells));
+        self
+    }
+
+    /// Adds an array which is filled by the public, followed by its size.
+    ///
+    /// The array is copied back to `out` after the call.
     ///
     /// # Examples
     ///
     /// ```
-    /// #[macro_use] extern crate samp_sdk;
-    /// use samp_sdk::types::Cell;
-    /// use std::ffi::{CStr,CString};
     /// use samp_sdk::amx::{AMX, AmxResult};
+    /// use samp_sdk::types::Cell;
     ///
-    /// // native: rot13(const source[], dest[], size=sizeof(dest));
-    /// // define_native!(n_rot13, source: CString, dest_ptr: &mut types::Cell, size: usize);
+    /// // forward GetPlayerStats(playerid, stats[], size);
+    /// fn player_stats(amx: &AMX, player_id: i32) -> AmxResult<[Cell; 4]> {
+    ///     let mut stats = [0; 4];
     ///
-    /// fn n_rot13(amx: &AMX, source: CString, dest_ptr: &mut Cell, size: usize) -> AmxResult<Cell> {
-    ///     let roted = rot13(&source);
-    ///     unsafe { 
-    ///         amx.set_cstr_of_size(&roted, dest_ptr, size); 
-    ///     }
-    ///     Ok(0)
+    ///     amx.call_public("GetPlayerStats")
+    ///         .arg(player_id)
+    ///         .arg_out(&mut stats)
+    ///         .exec()?;
+    ///
+    ///     Ok(stats)
     /// }
+    /// ```
+    pub fn arg_out(mut self, out: &'a mut [Cell]) -> Self {
+        let size = out.len() as Cell;
+
+        self.args.push(Argument::Array(vec![0; out.len()]));
+        self.outs.push((self.args.len() - 1, out));
+        self.args.push(Argument::Cell(size));
+        self
+    }
+
+    /// Pushes all arguments and executes the public.
     ///
-    /// fn rot13(string: &CStr) -> CString {
-    ///      let alphabet: [u8; 26] = [
-    ///          b'a', b'b', b'c', b'd', b'e', b'f', b'g', b'h', b'i', b'j', b'k', b'l', b'm',
-    ///          b'n', b'o', b'p', b'q', b'r', b's', b't', b'u', b'v', b'w', b'x', b'y', b'z'
-    ///      ];
+    /// Returns a value returned by the public or an error if the public isn't found.
+    /// If an argument can't be pushed, the already pushed ones are taken back from the stack.
     ///
-    ///      CString::new::<Vec<u8>>(
-    ///         string.to_bytes().into_iter()
-    ///            .map(|c| *alphabet.iter()
-    ///                              .chain(alphabet.iter())
-    ///                              .skip_while(|&x| *x != *c)
-    ///                              .nth(13)
-    ///                              .unwrap_or(&c))
-    ///            .collect()
-    ///      ).unwrap()
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::amx::AmxError;
+    /// use samp_sdk::testing::TestAmx;
+    ///
+    /// // The stack has room for only four arguments.
+    /// let mut test = TestAmx::new(&[], 20);
+    /// test.add_public("OnPlayerText", |_, args| args.len() as i32);
+    ///
+    /// let amx = test.amx();
+    /// let stk = amx.stk();
+    ///
+    /// let call = amx.call_public("OnPlayerText").arg(1).arg(2).arg(3).arg(4).arg(5).arg(6);
+    /// match call.exec() {
+    ///     Err(AmxError::StackError) => (),
+    ///     _ => panic!("six arguments don't fit the stack"),
     /// }
+    ///
+    /// assert_eq!(amx.stk(), stk);
+    /// assert_eq!(amx.call_public("OnPlayerText").arg(1).exec().unwrap(), 1);
     /// ```
-    pub unsafe fn set_cstr_of_size(&self, string: &CStr, dest_address: *mut Cell, allowed_length: usize) {
-        let bytes = string.to_bytes();
+    pub fn exec(self) -> AmxResult<Cell> {
+        let PublicCall { amx, index, args, outs } = self;
+        let index = index?;
+        let (stk, paramcount) = unsafe { ((*amx.amx).stk, (*amx.amx).paramcount) };
+        let mut first_addr = None;
+        let mut addrs = vec![None; args.len()];
+
+        let pushed = args.iter().enumerate().rev().try_for_each(|(position, arg)| {
+            let addr = match arg {
+                Argument::Cell(value) => return amx.push(*value),
+                Argument::String(string) => amx.push_string(string, false)?,
+                Argument::PackedString(string) => amx.push_string(string, true)?,
+                Argument::Array(cells) => amx.push_cells(cells)?,
+            };
 
-        // The following is the idiomatic way of doing this in Rust, as per Clippy. 
-        // All of it gets optimized away by the compiler.
-        for (position, byte) in bytes.iter().enumerate().take(allowed_length) {
-            *(dest_address.add(position)) = i32::from(*byte);
+            addrs[position] = Some(addr);
+
+            if first_addr.is_none() {
+                first_addr = Some(addr);
+            }
+
+            Ok(())
+        });
+
+        if pushed.is_err() {
+            // Don't leave a half of the arguments for the next call.
+            unsafe {
+                (*amx.amx).stk = stk;
+                (*amx.amx).paramcount = paramcount;
+            }
         }
 
-        *(dest_address.add( std::cmp::min(allowed_length,bytes.len()) )) = 0;
+        let result = pushed.and_then(|_| amx.exec(index)).and_then(|retval| {
+            for (position, out) in outs {
+                if let Some(addr) = addrs[position] {
+                    let first: &mut Cell = amx.get_address(addr)?;
+
+                    unsafe {
+                        ::std::ptr::copy_nonoverlapping(first as *const Cell, out.as_mut_ptr(), out.len());
+                    }
+                }
+            }
+
+            Ok(retval)
+        });
+
+        // The result of the public is more important than a failed release.
+        let released = first_addr.map_or(Ok(()), |addr| amx.release(addr));
+        result.and_then(|retval| released.map(|_| retval))
     }
+}
 
-    /// Raises an AMX error.
-    pub fn raise_error(&self, error: AmxError) -> AmxResult<()> {
-        let raise_error = import!(RaiseError);
-        call!(raise_error(self.amx, error as i32) => ())
+/// Memory allocated inside AMX by `AMX::allot_guarded`.
+///
+/// Releases the memory when dropped.
+pub struct Allocation<'a> {
+    amx: &'a AMX,
+    amx_addr: Cell,
+    phys_addr: usize,
+}
+
+impl<'a> Allocation<'a> {
+    /// The address of the memory relatived to AMX data section.
+    pub fn amx_addr(&self) -> Cell {
+        self.amx_addr
+    }
+
+    /// The physical address of the first allocated cell.
+    pub fn as_phys_ptr(&self) -> *mut Cell {
+        self.phys_addr as *mut Cell
+    }
+}
+
+impl<'a> Drop for Allocation<'a> {
+    fn drop(&mut self) {
+        let _ = self.amx.release(self.amx_addr);
     }
 }
 
@@ -727,61 +2838,181 @@ impl AMX {
 ///     Err(error)
 /// }
 /// ```
-#[derive(Fail, Debug)]
-#[fail(display = "AMX Error.")]
+///
+/// `AmxError` implements `std::error::Error`, so it can be boxed or propagated with `?`:
+///
+/// ```
+/// use samp_sdk::amx::AmxError;
+///
+/// let error: Box<dyn std::error::Error> = Box::new(AmxError::NotFound);
+/// assert_eq!(error.to_string(), "Function not found.");
+/// ```
+#[derive(Debug)]
+#[repr(i32)]
 pub enum AmxError {
-    #[fail(display = "Exit AMX.")]
     Exit = 1,
-    #[fail(display = "Assertation error.")]
     Assert = 2,
-    #[fail(display = "Stack error.")]
     StackError = 3,
-    #[fail(display = "Out of bounds.")]
     Bounds = 4,
-    #[fail(display = "Can't access to memory area.")]
     MemoryAccess = 5,
-    #[fail(display = "Invalid instruction.")]
     InvalidInstruction = 6,
-    #[fail(display = "Stack low.")]
     StackLow = 7,
-    #[fail(display = "Heap low.")]
     HeapLow = 8,
-    #[fail(display = "Public error.")]
     Callback = 9,
-    #[fail(display = "Native error.")]
     Native = 10,
-    #[fail(display = "Divide error.")]
     Divide = 11,
-    #[fail(display = "AMX is sleeping.")]
     Sleep = 12,
-    #[fail(display = "Invalid state.")]
     InvalidState = 13,
-    #[fail(display = "Out of memory.")]
     Memory = 16,
-    #[fail(display = "Wrong format.")]
     Format = 17,
-    #[fail(display = "Incorrect AMX version.")]
     Version = 18,
-    #[fail(display = "Function not found.")]
     NotFound = 19,
-    #[fail(display = "Incorrect public index.")]
     Index = 20,
-    #[fail(display = "Debug error.")]
     Debug = 21,
-    #[fail(display = "Error while init AMX.")]
     Init = 22,
-    #[fail(display = "Incorrect user data.")]
     UserData = 23,
-    #[fail(display = "Error while init JIT.")]
     InitJit = 24,
-    #[fail(display = "Error in arguments.")]
     Params = 25,
-    #[fail(display = "Domain error.")]
     Domain = 26,
-    #[fail(display = "General error.")]
     General = 27,
-    #[fail(display = "Unknown error.")]
     Unknown,
+    /// The server hasn't given the plugin a function it needs, e.g. the plugin isn't loaded yet.
+    NullExport,
+    /// A string can't be converted from or to cp1251.
+    EncodingError,
+    /// A string isn't valid UTF-8.
+    Utf8,
+    WithContext {
+        kind: Box<AmxError>,
+        public: Option<String>,
+        cip: Option<i32>,
+    },
+}
+
+impl AmxError {
+    /// Returns the error without its context.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::amx::{AMX, AmxError};
+    ///
+    /// fn is_sleeping(amx: &AMX, index: i32) -> bool {
+    ///     match amx.exec(index) {
+    ///         Err(err) => match err.kind() {
+    ///             AmxError::Sleep => true,
+    ///             _ => false,
+    ///         },
+    ///         Ok(_) => false,
+    ///     }
+    /// }
+    /// ```
+    pub fn kind(&self) -> &AmxError {
+        match self {
+            AmxError::WithContext { kind, .. } => kind.kind(),
+            _ => self,
+        }
+    }
+
+    /// Returns the raw AMX error code.
+    ///
+    /// Errors of the SDK itself don't have their own codes, so they are given the closest AMX ones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::amx::AmxError;
+    ///
+    /// assert_eq!(AmxError::NullExport.code(), AmxError::NotFound.code());
+    /// assert_eq!(AmxError::EncodingError.code(), AmxError::Format.code());
+    /// assert_eq!(AmxError::Utf8.code(), AmxError::Format.code());
+    ///
+    /// // They never come from the AMX.
+    /// match AmxError::from(AmxError::Utf8.code()) {
+    ///     AmxError::Format => (),
+    ///     _ => panic!("17 is AMX_ERR_FORMAT"),
+    /// }
+    /// ```
+    pub fn code(&self) -> i32 {
+        match self.kind() {
+            AmxError::Exit => 1,
+            AmxError::Assert => 2,
+            AmxError::StackError => 3,
+            AmxError::Bounds => 4,
+            AmxError::MemoryAccess => 5,
+            AmxError::InvalidInstruction => 6,
+            AmxError::StackLow => 7,
+            AmxError::HeapLow => 8,
+            AmxError::Callback => 9,
+            AmxError::Native => 10,
+            AmxError::Divide => 11,
+            AmxError::Sleep => 12,
+            AmxError::InvalidState => 13,
+            AmxError::Memory => 16,
+            AmxError::Format => 17,
+            AmxError::Version => 18,
+            AmxError::NotFound => 19,
+            AmxError::Index => 20,
+            AmxError::Debug => 21,
+            AmxError::Init => 22,
+            AmxError::UserData => 23,
+            AmxError::InitJit => 24,
+            AmxError::Params => 25,
+            AmxError::Domain => 26,
+            AmxError::General => 27,
+            AmxError::Unknown => 28,
+            AmxError::NullExport => 19,
+            AmxError::EncodingError => 17,
+            AmxError::Utf8 => 17,
+            AmxError::WithContext { .. } => unreachable!(),
+        }
+    }
+}
+
+impl fmt::Display for AmxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AmxError::Exit => write!(f, "Exit AMX."),
+            AmxError::Assert => write!(f, "Assertation error."),
+            AmxError::StackError => write!(f, "Stack error."),
+            AmxError::Bounds => write!(f, "Out of bounds."),
+            AmxError::MemoryAccess => write!(f, "Can't access to memory area."),
+            AmxError::InvalidInstruction => write!(f, "Invalid instruction."),
+            AmxError::StackLow => write!(f, "Stack low."),
+            AmxError::HeapLow => write!(f, "Heap low."),
+            AmxError::Callback => write!(f, "Public error."),
+            AmxError::Native => write!(f, "Native error."),
+            AmxError::Divide => write!(f, "Divide error."),
+            AmxError::Sleep => write!(f, "AMX is sleeping."),
+            AmxError::InvalidState => write!(f, "Invalid state."),
+            AmxError::Memory => write!(f, "Out of memory."),
+            AmxError::Format => write!(f, "Wrong format."),
+            AmxError::Version => write!(f, "Incorrect AMX version."),
+            AmxError::NotFound => write!(f, "Function not found."),
+            AmxError::Index => write!(f, "Incorrect public index."),
+            AmxError::Debug => write!(f, "Debug error."),
+            AmxError::Init => write!(f, "Error while init AMX."),
+            AmxError::UserData => write!(f, "Incorrect user data."),
+            AmxError::InitJit => write!(f, "Error while init JIT."),
+            AmxError::Params => write!(f, "Error in arguments."),
+            AmxError::Domain => write!(f, "Domain error."),
+            AmxError::General => write!(f, "General error."),
+            AmxError::Unknown => write!(f, "Unknown error."),
+            AmxError::NullExport => write!(f, "AMX function isn't available."),
+            AmxError::EncodingError => write!(f, "Can't convert the string from or to cp1251."),
+            AmxError::Utf8 => write!(f, "Invalid UTF-8."),
+            AmxError::WithContext { kind, public, cip } => write!(f, "{} (public: {:?}, cip: {:?})", kind, public, cip),
+        }
+    }
+}
+
+impl Error for AmxError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AmxError::WithContext { kind, .. } => Some(kind.as_ref()),
+            _ => None,
+        }
+    }
 }
 
 impl From<i32> for AmxError {
@@ -3,18 +3,31 @@
 
     Used in `expand_args!`.
 */
-use crate::types::Cell;
+use std::ffi::CString;
+use std::mem::size_of;
+use std::str::Chars;
+
+use crate::amx::{AMX, AmxResult, AmxError};
+use crate::types::{Cell, Float};
 
 pub struct Parser {
     params: *mut Cell,
     index: isize,
+    count: isize,
 }
 
 impl Parser {
+    /// `params` must point to native arguments, it is called from natives defined by `define_native!`.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
     pub fn new(params: *mut Cell) -> Parser {
+        let count = unsafe {
+            (std::ptr::read(params) as isize / size_of::<Cell>() as isize).max(0)
+        };
+
         Parser {
             params, 
             index: 0,
+            count,
         }
     }
 
@@ -25,4 +38,242 @@ impl Parser {
             self.params.offset(self.index)
         }
     }
-}
\ No newline at end of file
+
+    /// Checks if the caller has passed one more argument.
+    pub fn has_next(&self) -> bool {
+        self.index < self.count
+    }
+
+    /// Reads a value of the next argument.
+    ///
+    /// Returns `None` if there are no more arguments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::args::Parser;
+    ///
+    /// // Two arguments: 10 and 20.
+    /// let mut params = [8, 10, 20];
+    /// let mut parser = Parser::new(params.as_mut_ptr());
+    ///
+    /// assert_eq!(parser.remaining(), 2);
+    /// assert_eq!(parser.next_cell(), Some(10));
+    /// assert_eq!(parser.remaining(), 1);
+    /// assert_eq!(parser.next_cell(), Some(20));
+    /// assert_eq!(parser.next_cell(), None);
+    /// assert_eq!(parser.remaining(), 0);
+    /// ```
+    pub fn next_cell(&mut self) -> Option<Cell> {
+        if self.has_next() {
+            Some(unsafe { std::ptr::read(self.next()) })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a count of passed arguments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::args::Parser;
+    ///
+    /// let mut params = [16, 1, 2, 3, 4];
+    /// let parser = Parser::new(params.as_mut_ptr());
+    ///
+    /// assert_eq!(parser.arg_count(), 4);
+    /// assert_eq!(parser.bytes(), 16);
+    ///
+    /// let mut broken = [-4];
+    /// assert_eq!(Parser::new(broken.as_mut_ptr()).arg_count(), 0);
+    /// ```
+    pub fn arg_count(&self) -> usize {
+        self.count as usize
+    }
+
+    /// Returns a size of passed arguments in bytes, as the server gives it in the first cell.
+    pub fn bytes(&self) -> Cell {
+        unsafe {
+            std::ptr::read(self.params)
+        }
+    }
+
+    /// Returns how many arguments haven't been read yet.
+    pub fn remaining(&self) -> usize {
+        (self.count - self.index).max(0) as usize
+    }
+}
+
+/// A value of a variadic argument, see `VarArgs`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PassedArgument {
+    Int(Cell),
+    Float(f32),
+    Str(String),
+}
+
+/// Iterator over variadic arguments of a native, typed by a format string.
+///
+/// Pawn passes variadic arguments by reference, so each of them is an address in AMX memory.
+/// Format letters are `i` or `d` for an integer, `f` for a float and `s` for a string decoded from cp1251.
+/// An unknown letter gives `AmxError::Format` and a missing argument gives `AmxError::Params`,
+/// after which the iterator ends.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::args::{Parser, PassedArgument, VarArgs};
+/// use samp_sdk::testing::{self, TestAmx};
+/// use samp_sdk::types::Cell;
+/// # use samp_sdk::amx::AmxError;
+///
+/// // The script's variables at the addresses 0, 4 and 8.
+/// let test = TestAmx::new(&[5, 7, 1.5f32.to_bits() as Cell], 16);
+/// let amx = test.amx();
+///
+/// // native: SetTimerEx(const callback[], interval, repeating, const format[], {Float, _}:...);
+/// // The first four arguments are read by the native, then the addresses of the variadic ones.
+/// let mut params = testing::params(&[0, 1000, 0, 0, 0, 4, 8]);
+/// let mut parser = Parser::new(params.as_mut_ptr());
+///
+/// for _ in 0..4 {
+///     parser.next();
+/// }
+///
+/// let values: Vec<_> = VarArgs::new(&amx, parser, "iif").collect();
+///
+/// assert_eq!(values[0].as_ref().ok(), Some(&PassedArgument::Int(5)));
+/// assert_eq!(values[1].as_ref().ok(), Some(&PassedArgument::Int(7)));
+/// assert_eq!(values[2].as_ref().ok(), Some(&PassedArgument::Float(1.5)));
+/// assert_eq!(values.len(), 3);
+/// #
+/// # // A missing argument.
+/// # let mut short = testing::params(&[0, 4]);
+/// #
+/// # match VarArgs::new(&amx, Parser::new(short.as_mut_ptr()), "iii").last() {
+/// #     Some(Err(AmxError::Params)) => (),
+/// #     _ => panic!("the script has passed only 2 arguments"),
+/// # }
+/// ```
+pub struct VarArgs<'a> {
+    amx: &'a AMX,
+    parser: Parser,
+    format: Chars<'a>,
+}
+
+impl<'a> VarArgs<'a> {
+    /// Makes an iterator over arguments which are left in `parser`.
+    pub fn new(amx: &'a AMX, parser: Parser, format: &'a str) -> VarArgs<'a> {
+        VarArgs {
+            amx,
+            parser,
+            format: format.chars(),
+        }
+    }
+
+    fn read(&mut self, specifier: char) -> AmxResult<PassedArgument> {
+        if !self.parser.has_next() {
+            return Err(AmxError::Params);
+        }
+
+        let param = self.parser.next();
+        let address = unsafe { std::ptr::read(param) };
+
+        match specifier {
+            'i' | 'd' => self.amx.get_address::<Cell>(address).map(|value| PassedArgument::Int(*value)),
+            'f' => self.amx.get_address::<f32>(address).map(|value| PassedArgument::Float(*value)),
+            's' => self.amx.get_cstring(param)
+                .and_then(|string| crate::cp1251::decode(string.as_bytes()))
+                .map(PassedArgument::Str),
+            _ => Err(AmxError::Format),
+        }
+    }
+}
+
+impl Iterator for VarArgs<'_> {
+    type Item = AmxResult<PassedArgument>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let specifier = self.format.next()?;
+        let result = self.read(specifier);
+
+        if result.is_err() {
+            self.format = "".chars();
+        }
+
+        Some(result)
+    }
+}
+
+/// A value which can be read from an argument of a native, see `AMX::read_args`.
+pub trait FromArg: Sized {
+    /// Reads the value from the next arguments of `parser`.
+    fn from_arg(amx: &AMX, parser: &mut Parser) -> AmxResult<Self>;
+}
+
+impl FromArg for Cell {
+    fn from_arg(_: &AMX, parser: &mut Parser) -> AmxResult<Cell> {
+        parser.next_cell().ok_or(AmxError::Params)
+    }
+}
+
+impl FromArg for bool {
+    fn from_arg(amx: &AMX, parser: &mut Parser) -> AmxResult<bool> {
+        Cell::from_arg(amx, parser).map(|cell| cell != 0)
+    }
+}
+
+impl FromArg for f32 {
+    fn from_arg(amx: &AMX, parser: &mut Parser) -> AmxResult<f32> {
+        Cell::from_arg(amx, parser).map(|cell| f32::from_bits(cell as u32))
+    }
+}
+
+impl FromArg for Float {
+    fn from_arg(amx: &AMX, parser: &mut Parser) -> AmxResult<Float> {
+        Cell::from_arg(amx, parser).map(Float::from)
+    }
+}
+
+impl FromArg for CString {
+    fn from_arg(amx: &AMX, parser: &mut Parser) -> AmxResult<CString> {
+        if !parser.has_next() {
+            return Err(AmxError::Params);
+        }
+
+        amx.get_cstring(parser.next())
+    }
+}
+
+impl FromArg for String {
+    fn from_arg(amx: &AMX, parser: &mut Parser) -> AmxResult<String> {
+        CString::from_arg(amx, parser).and_then(|string| crate::cp1251::decode(string.as_bytes()))
+    }
+}
+
+/// A tuple of values read from arguments in order, see `AMX::read_args`.
+pub trait FromArgs: Sized {
+    fn from_args(amx: &AMX, parser: &mut Parser) -> AmxResult<Self>;
+}
+
+macro_rules! impl_from_args {
+    ($( $type:ident ),*) => {
+        impl<$( $type: FromArg ),*> FromArgs for ($( $type, )*) {
+            #[allow(unused_variables)]
+            fn from_args(amx: &AMX, parser: &mut Parser) -> AmxResult<Self> {
+                Ok(($( $type::from_arg(amx, parser)?, )*))
+            }
+        }
+    };
+}
+
+impl_from_args!();
+impl_from_args!(A);
+impl_from_args!(A, B);
+impl_from_args!(A, B, C);
+impl_from_args!(A, B, C, D);
+impl_from_args!(A, B, C, D, E);
+impl_from_args!(A, B, C, D, E, F);
+impl_from_args!(A, B, C, D, E, F, G);
+impl_from_args!(A, B, C, D, E, F, G, H);
@@ -0,0 +1,84 @@
+/*!
+    Registry of publics which a plugin calls from Rust.
+
+    Indices of publics are found once per AMX and cached.
+*/
+
+use std::collections::HashMap;
+
+use crate::amx::{AMX, AmxError, AmxResult, PublicCall};
+
+/// Publics that a plugin calls in every loaded AMX.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::amx::{AMX, AmxResult};
+/// use samp_sdk::callbacks::Callbacks;
+/// use samp_sdk::types::Cell;
+///
+/// struct Plugin {
+///     callbacks: Callbacks,
+/// }
+///
+/// impl Plugin {
+///     fn load(&mut self) -> bool {
+///         self.callbacks.register("OnPlayerTeleported");
+///         true
+///     }
+///
+///     fn amx_unload(&mut self, amx: &AMX) -> Cell {
+///         self.callbacks.forget(amx);
+///         0
+///     }
+///
+///     fn notify(&mut self, amx: &AMX, player_id: i32) -> AmxResult<Cell> {
+///         // AmxError::NotFound if this AMX doesn't have OnPlayerTeleported.
+///         self.callbacks.call(amx, "OnPlayerTeleported")?
+///             .arg(player_id)
+///             .exec()
+///     }
+/// }
+/// ```
+#[derive(Default)]
+pub struct Callbacks {
+    publics: HashMap<String, HashMap<usize, Option<i32>>>,
+}
+
+impl Callbacks {
+    pub fn new() -> Callbacks {
+        Callbacks::default()
+    }
+
+    /// Adds a public to the registry.
+    pub fn register(&mut self, name: &str) {
+        self.publics.entry(name.to_owned()).or_default();
+    }
+
+    /// Makes a builder that calls a registered public in given AMX.
+    ///
+    /// Returns `AmxError::NotFound` if the public isn't registered or the AMX doesn't have it.
+    pub fn call<'a>(&mut self, amx: &'a AMX, name: &str) -> AmxResult<PublicCall<'a>> {
+        let indices = self.publics.get_mut(name).ok_or(AmxError::NotFound)?;
+
+        let index = match indices.get(&(amx.amx as usize)) {
+            Some(index) => *index,
+            None => {
+                let index = amx.find_public(name).ok();
+                indices.insert(amx.amx as usize, index);
+                index
+            },
+        };
+
+        index
+            .map(|index| amx.call_public_index(index))
+            .ok_or(AmxError::NotFound)
+    }
+
+    /// Forgets indices found in given AMX. Should be called when the AMX is unloaded.
+    pub fn forget(&mut self, amx: &AMX) {
+        for indices in self.publics.values_mut() {
+            indices.remove(&(amx.amx as usize));
+        }
+    }
+}
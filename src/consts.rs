@@ -56,6 +56,10 @@ pub const AMX_FLAG_RELOC: u16 = 0x8000;
 pub const AMX_EXEC_MAIN: i32 = -1;
 pub const AMX_EXEC_CONT: i32 = -2;
 
+pub const AMX_NAME_MAX: usize = 31;
+
+pub const AMX_MAGIC: u16 = 0xF1E0;
+
 pub enum Exports {
     Align16 = 0,
     Align32 = 1,
@@ -101,4 +105,49 @@ pub enum Exports {
     UTF8Get = 41,
     UTF8Len = 42,
     UTF8Put = 43,
-}
\ No newline at end of file
+}
+
+/// SA:MP constants from `a_samp.inc` and `a_players.inc`.
+///
+/// Unlike the constants above these are meant to be used in plugins.
+/// They are cells, like the values scripts pass to natives.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::consts::samp;
+///
+/// assert_eq!(samp::MAX_PLAYERS, 1000);
+/// assert_eq!(samp::MAX_PLAYER_NAME, 24);
+/// assert_eq!(samp::MAX_VEHICLES, 2000);
+/// assert_eq!(samp::INVALID_PLAYER_ID, 0xFFFF);
+/// assert_eq!(samp::INVALID_VEHICLE_ID, 0xFFFF);
+/// assert_eq!(samp::NO_TEAM, 255);
+/// ```
+pub mod samp {
+    use crate::types::Cell;
+
+    pub const MAX_PLAYER_NAME: Cell = 24;
+    pub const MAX_PLAYERS: Cell = 1000;
+    pub const MAX_VEHICLES: Cell = 2000;
+    pub const MAX_ACTORS: Cell = 1000;
+    pub const MAX_OBJECTS: Cell = 1000;
+    pub const MAX_GANG_ZONES: Cell = 1024;
+    pub const MAX_TEXT_DRAWS: Cell = 2048;
+    pub const MAX_PLAYER_TEXT_DRAWS: Cell = 256;
+    pub const MAX_MENUS: Cell = 128;
+    pub const MAX_3DTEXT_GLOBAL: Cell = 1024;
+    pub const MAX_3DTEXT_PLAYER: Cell = 1024;
+    pub const MAX_PICKUPS: Cell = 4096;
+
+    pub const INVALID_PLAYER_ID: Cell = 0xFFFF;
+    pub const INVALID_VEHICLE_ID: Cell = 0xFFFF;
+    pub const INVALID_ACTOR_ID: Cell = 0xFFFF;
+    pub const INVALID_OBJECT_ID: Cell = 0xFFFF;
+    pub const INVALID_MENU: Cell = 0xFF;
+    pub const INVALID_TEXT_DRAW: Cell = 0xFFFF;
+    pub const INVALID_GANG_ZONE: Cell = -1;
+    pub const INVALID_3DTEXT_ID: Cell = 0xFFFF;
+
+    pub const NO_TEAM: Cell = 255;
+}
@@ -1,20 +1,84 @@
+use std::borrow::Cow;
+
 use encoding::{Encoding, EncoderTrap, DecoderTrap};
 use encoding::all::WINDOWS_1251;
 
 use crate::amx::{AmxResult, AmxError};
 
+/// Encodes a string to cp1251.
+///
+/// Returns `AmxError::EncodingError` if cp1251 doesn't have some of its characters.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::amx::AmxError;
+/// use samp_sdk::cp1251;
+///
+/// assert_eq!(cp1251::encode("Hi!").unwrap(), b"Hi!");
+///
+/// match cp1251::encode("Hi 😀!") {
+///     Err(AmxError::EncodingError) => (),
+///     _ => panic!("cp1251 has no emoji"),
+/// }
+/// ```
 pub fn encode(string: &str) -> AmxResult<Vec<u8>> {
-    WINDOWS_1251.encode(string, EncoderTrap::Strict).map_err(|_| AmxError::Format)
+    WINDOWS_1251.encode(string, EncoderTrap::Strict).map_err(|_| AmxError::EncodingError)
+}
+
+/// Encodes a string replacing characters which cp1251 doesn't have with `?`.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::cp1251;
+///
+/// assert_eq!(cp1251::encode_lossy("Hi 😀!"), b"Hi ?!");
+/// assert_eq!(cp1251::encode_lossy("Привет"), vec![0xCF, 0xF0, 0xE8, 0xE2, 0xE5, 0xF2]);
+/// ```
+pub fn encode_lossy(string: &str) -> Vec<u8> {
+    WINDOWS_1251.encode(string, EncoderTrap::Replace).unwrap_or_default()
 }
 
 pub fn decode(bytes: &[u8]) -> AmxResult<String> {
-    WINDOWS_1251.decode(bytes, DecoderTrap::Strict).map_err(|_| AmxError::Format)
+    decode_cow(bytes).map(Cow::into_owned)
+}
+
+/// Decodes a string, borrowing the bytes if they are ASCII.
+///
+/// ASCII is the same in cp1251 and UTF-8, so such strings are neither looked up in the codepage nor copied.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::cp1251;
+/// use std::borrow::Cow;
+///
+/// match cp1251::decode_cow(b"/help") {
+///     Ok(Cow::Borrowed(command)) => assert_eq!(command, "/help"),
+///     _ => panic!("ASCII should be borrowed"),
+/// }
+///
+/// match cp1251::decode_cow(&[0xCF, 0xF0, 0xE8, 0xE2, 0xE5, 0xF2]) {
+///     Ok(Cow::Owned(greeting)) => assert_eq!(greeting, "Привет"),
+///     _ => panic!("cp1251 should be decoded"),
+/// }
+/// ```
+pub fn decode_cow(bytes: &[u8]) -> AmxResult<Cow<'_, str>> {
+    if bytes.is_ascii() {
+        // ASCII is always valid UTF-8.
+        Ok(Cow::Borrowed(unsafe { ::std::str::from_utf8_unchecked(bytes) }))
+    } else {
+        WINDOWS_1251.decode(bytes, DecoderTrap::Strict)
+            .map(Cow::Owned)
+            .map_err(|_| AmxError::EncodingError)
+    }
 }
 
 pub fn encode_to(source: &str, dest: &mut Vec<u8>) -> AmxResult<()> {
-    WINDOWS_1251.encode_to(source, EncoderTrap::Strict, dest).map_err(|_| AmxError::Format)
+    WINDOWS_1251.encode_to(source, EncoderTrap::Strict, dest).map_err(|_| AmxError::EncodingError)
 }
 
 pub fn decode_to(source: &[u8], dest: &mut String) -> AmxResult<()> {
-    WINDOWS_1251.decode_to(source, DecoderTrap::Strict, dest).map_err(|_| AmxError::Format)
+    WINDOWS_1251.decode_to(source, DecoderTrap::Strict, dest).map_err(|_| AmxError::EncodingError)
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
 /*!
-    Raw pointers to logprintf and the list of AMX functions.
+    Raw pointers to logprintf, the list of AMX functions and loaded AMX instances.
 
     Do **not** use it directly.
 
@@ -7,14 +7,42 @@
 */
 
 use std;
+use std::collections::{HashMap, HashSet};
 use std::sync::Mutex;
+use std::sync::atomic::AtomicUsize;
 use crate::types::Logprintf_t;
+use crate::amx::{AMX, AmxResult};
+use crate::consts::PLUGIN_DATA_AMX_EXPORTS;
 use crate::lazy_static;
 
 lazy_static! {
     pub static ref logprintf: Mutex<Logprintf_t> = unsafe {
         Mutex::new(std::mem::transmute(0usize))
     };
+
+    pub static ref amx_list: Mutex<HashSet<usize>> = Mutex::new(HashSet::new());
+
+    pub static ref debug_hooks: Mutex<HashMap<usize, DebugHook>> = Mutex::new(HashMap::new());
 }
 
-pub static mut amx_functions: *const u32 = 0 as *const u32;
\ No newline at end of file
+/// A closure set by `AMX::set_debug_closure`.
+pub type DebugHook = Box<dyn FnMut(&AMX) -> AmxResult<()> + Send>;
+
+/// The largest count of cells `AMX::allot` asks for, see `amx::set_allot_limit`.
+pub static allot_limit: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// The table of AMX functions, one pointer per `consts::Exports` entry.
+pub static mut amx_functions: *const usize = 0 as *const usize;
+
+/// Sets `logprintf` and `amx_functions` from the data given to `Load` by the server.
+///
+/// Called from `Load` of `new_plugin!`, may be called again if the server gives new data.
+///
+/// # Safety
+/// `data` must be the array of plugin data given by the server.
+pub unsafe fn refresh(data: *const *const u32) {
+    let mut log = logprintf.lock().unwrap();
+
+    *log = *(data as *const Logprintf_t);
+    amx_functions = std::ptr::read(data.offset(PLUGIN_DATA_AMX_EXPORTS as isize)) as *const usize;
+}
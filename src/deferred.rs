@@ -0,0 +1,99 @@
+/*!
+    Publics called on the next server tick.
+
+    Calling a public from a native runs the script re-entrantly, while the server is still
+    in the middle of the native call. Queueing the call and making it from `process_tick` is safer.
+*/
+
+use std::collections::VecDeque;
+
+use crate::amx::{self, AmxIdent, AmxResult, Argument};
+use crate::types::Cell;
+
+struct DeferredCall {
+    amx: AmxIdent,
+    name: String,
+    args: Vec<Argument>,
+}
+
+/// A queue of public calls made later by `flush`.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::amx::{AMX, AmxIdent, AmxResult, Argument};
+/// use samp_sdk::deferred::DeferredCalls;
+/// use samp_sdk::types::{self, Cell};
+///
+/// struct Plugin {
+///     deferred: DeferredCalls,
+/// }
+///
+/// impl Plugin {
+///     // native: KickLater(playerid);
+///     fn kick_later(&mut self, amx: &AMX, player_id: Cell) -> AmxResult<Cell> {
+///         self.deferred.queue_public(AmxIdent::from(amx), "OnPlayerKicked", vec![Argument::Cell(player_id)]);
+///         Ok(1)
+///     }
+///
+///     fn process_tick(&mut self) {
+///         self.deferred.flush();
+///     }
+/// }
+///
+/// // Calls of an AMX which isn't loaded are dropped.
+/// let mut plugin = Plugin { deferred: DeferredCalls::new() };
+/// let amx = AMX::new(0x1000 as *mut types::AMX);
+///
+/// plugin.kick_later(&amx, 1).unwrap();
+/// assert_eq!(plugin.deferred.len(), 1);
+///
+/// assert!(plugin.deferred.flush().is_empty());
+/// assert!(plugin.deferred.is_empty());
+/// ```
+#[derive(Default)]
+pub struct DeferredCalls {
+    queue: VecDeque<DeferredCall>,
+}
+
+impl DeferredCalls {
+    pub fn new() -> DeferredCalls {
+        DeferredCalls::default()
+    }
+
+    /// Adds a call of a public to the queue.
+    ///
+    /// Arguments are in the same order as in the public's declaration.
+    pub fn queue_public(&mut self, amx: AmxIdent, name: &str, args: Vec<Argument>) {
+        self.queue.push_back(DeferredCall {
+            amx,
+            name: name.to_owned(),
+            args,
+        });
+    }
+
+    /// Returns a count of queued calls.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Calls all queued publics in the order they were queued and empties the queue.
+    ///
+    /// Calls of AMX instances which have been unloaded are dropped.
+    /// Returns results of made calls.
+    pub fn flush(&mut self) -> Vec<AmxResult<Cell>> {
+        self.queue.drain(..)
+            .filter_map(|call| {
+                amx::get(call.amx).map(|amx| {
+                    call.args.into_iter()
+                        .fold(amx.call_public(&call.name), |public, arg| public.argument(arg))
+                        .exec()
+                })
+            })
+            .collect()
+    }
+}
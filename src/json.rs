@@ -0,0 +1,125 @@
+/*!
+    Reading of AMX arrays into JSON values.
+
+    Pawn has no way to describe a layout of an array, so a plugin gives a schema:
+    a list of named fields in the order they are stored in the array.
+    It's the usual way to pass an `enum` structure to a web service or a database.
+
+    Available with the `serde` feature.
+*/
+use std::convert::TryFrom;
+use std::mem::size_of;
+
+use serde_json::{Map, Number, Value};
+
+use crate::amx::{AMX, AmxResult, AmxError};
+use crate::cp1251;
+use crate::types::Cell;
+
+/// A type of a field of an array.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Field {
+    /// One cell with an integer.
+    Int,
+    /// One cell with a `Float:` value.
+    Float,
+    /// An unpacked string taking a given count of cells, decoded from cp1251.
+    String(usize),
+}
+
+impl Field {
+    /// Returns a count of cells the field takes.
+    pub fn cells(self) -> usize {
+        match self {
+            Field::Int | Field::Float => 1,
+            Field::String(size) => size,
+        }
+    }
+}
+
+/// Reads an array at `address` to a JSON object with fields of the schema.
+///
+/// Returns `AmxError::MemoryAccess` if the array doesn't fit in AMX memory,
+/// `AmxError::EncodingError` if a string can't be decoded and `AmxError::Domain` if a float is NaN or infinite.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::json::{self, Field};
+/// use samp_sdk::testing::TestAmx;
+/// use samp_sdk::types::Cell;
+///
+/// // enum E_PLAYER { E_PLAYER_ID, Float:E_PLAYER_HEALTH, E_PLAYER_NAME[4] }
+/// let test = TestAmx::new(&[7, 75.5f32.to_bits() as Cell, 'B' as Cell, 'o' as Cell, 'b' as Cell, 0], 16);
+/// let amx = test.amx();
+///
+/// let schema = [("id", Field::Int), ("health", Field::Float), ("name", Field::String(4))];
+/// let player = json::read_object(&amx, 0, &schema).unwrap();
+///
+/// assert_eq!(player.to_string(), r#"{"health":75.5,"id":7,"name":"Bob"}"#);
+/// # assert!(json::read_object(&amx, 4, &schema).is_err());
+/// ```
+pub fn read_object(amx: &AMX, address: Cell, schema: &[(&str, Field)]) -> AmxResult<Value> {
+    let mut object = Map::new();
+    let mut index = 0;
+
+    for &(name, field) in schema {
+        object.insert(name.to_owned(), read_field(amx, address, index, field)?);
+        index += field.cells();
+    }
+
+    Ok(Value::Object(object))
+}
+
+/// Reads `count` consecutive objects of the schema, e.g. a two-dimensional array of enum structures.
+///
+/// Unlike a Pawn 2D array, the rows are expected to follow each other without the indirection table,
+/// as they are in a one-dimensional array of `count * size` cells.
+pub fn read_array(amx: &AMX, address: Cell, schema: &[(&str, Field)], count: usize) -> AmxResult<Value> {
+    let size: usize = schema.iter().map(|&(_, field)| field.cells()).sum();
+    let mut array = Vec::with_capacity(count);
+
+    for row in 0..count {
+        let offset = row.checked_mul(size)
+            .and_then(|cells| cells.checked_mul(size_of::<Cell>()))
+            .and_then(|bytes| Cell::try_from(bytes).ok())
+            .and_then(|bytes| address.checked_add(bytes))
+            .ok_or(AmxError::MemoryAccess)?;
+
+        array.push(read_object(amx, offset, schema)?);
+    }
+
+    Ok(Value::Array(array))
+}
+
+fn read_field(amx: &AMX, address: Cell, index: usize, field: Field) -> AmxResult<Value> {
+    let cell = |offset: usize| {
+        let index = Cell::try_from(index + offset).map_err(|_| AmxError::MemoryAccess)?;
+        amx.get_cell(address, index)
+    };
+
+    match field {
+        Field::Int => Ok(Value::from(cell(0)?)),
+        Field::Float => {
+            let float = f64::from(f32::from_bits(cell(0)? as u32));
+            Number::from_f64(float).map(Value::Number).ok_or(AmxError::Domain)
+        }
+        Field::String(size) => {
+            // The whole field must be in AMX memory, even if the string ends earlier.
+            if size > 0 {
+                cell(size - 1)?;
+            }
+
+            let mut bytes = Vec::with_capacity(size);
+
+            for offset in 0..size {
+                match cell(offset)? {
+                    0 => break,
+                    byte => bytes.push(byte as u8),
+                }
+            }
+
+            cp1251::decode(&bytes).map(Value::String)
+        }
+    }
+}
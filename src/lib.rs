@@ -17,10 +17,18 @@ pub mod data;
 pub mod types;
 pub mod amx;
 pub mod cp1251;
+pub mod packed;
+pub mod logger;
+pub mod callbacks;
+pub mod deferred;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+#[cfg(feature = "serde")]
+pub mod json;
 
 pub use lazy_static::{lazy_static, __lazy_static_internal, __lazy_static_create};
 
 pub mod prelude {
     pub use crate::amx::{AMX, AmxResult, AmxError};
-    pub use crate::types::Cell;
+    pub use crate::types::{Cell, Float};
 }
\ No newline at end of file
@@ -0,0 +1,75 @@
+/*!
+    Implementation of `log::Log` that writes to SA:MP server output.
+
+    Lets plugins use `info!`, `warn!`, `error!` and other macros of `log` crate.
+*/
+
+use std::ffi::CString;
+use std::fmt::Display;
+
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use crate::data::logprintf;
+
+static LOGGER: Logger = Logger;
+
+/// Logger that prefixes every message with its level.
+pub struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            log_level(record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Sets `Logger` as the global logger with given maximum level.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::logger;
+/// use log::{info, LevelFilter};
+///
+/// fn load() -> bool {
+///     logger::init(LevelFilter::Info).unwrap();
+///     info!("My plugin is loaded!");
+///     true
+/// }
+/// ```
+pub fn init(level: LevelFilter) -> Result<(), SetLoggerError> {
+    log::set_logger(&LOGGER).map(|_| log::set_max_level(level))
+}
+
+/// Logs a message with a level prefix, e.g. `[WARN] message`.
+///
+/// Null bytes, which may come from strings of a script, are removed from the message.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::logger;
+/// use samp_sdk::testing::TestAmx;
+/// use log::Level;
+///
+/// let test = TestAmx::new(&[], 16);
+///
+/// logger::log_level(Level::Warn, "Name\0_Surname");
+/// assert_eq!(test.take_log(), vec!["[WARN] Name_Surname"]);
+/// ```
+pub fn log_level<T: Display>(level: Level, message: T) {
+    // A poisoned lock means a panic while logging, there is nothing better to do than to skip it.
+    if let Ok(printf) = logprintf.lock() {
+        let text = format!("[{}] {}", level, message).replace('\0', "");
+        // There are no null bytes left.
+        let c_text = CString::new(text).unwrap();
+        printf(c_text.as_ptr());
+    }
+}
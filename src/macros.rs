@@ -4,7 +4,11 @@ Some useful macros to easy access and define natives.
 Most of them hide raw C bindings and exports to make code easier to understand.
 */
 
-/// Clear macros that makes a new `Vec<AMX_NATIVE_INFO>`.
+/// Clear macros that makes a new `amx::Natives` list.
+///
+/// The list owns names of the natives and frees them when it's dropped,
+/// so it has to be alive while `AMX::register` is called.
+/// When all names are string literals they are stored in the plugin binary and aren't allocated.
 ///
 /// # Examples
 ///
@@ -24,42 +28,65 @@ Most of them hide raw C bindings and exports to make code easier to understand.
 ///     amx.register(&natives);
 /// }
 /// ```
+///
+/// Names are null-terminated C strings:
+///
+/// ```
+/// #[macro_use] extern crate samp_sdk;
+/// use samp_sdk::types;
+/// use std::ffi::CStr;
+///
+/// extern "C" fn show_something(_: *mut types::AMX, _: *mut i32) -> i32 { 0 }
+///
+/// # fn main() {
+/// let prefix = "Plugin";
+///
+/// let literal = natives!["ShowSomething" => show_something];
+/// let formatted = natives![format!("{}_ShowSomething", prefix) => show_something];
+///
+/// unsafe {
+///     assert_eq!(CStr::from_ptr(literal[0].name).to_str(), Ok("ShowSomething"));
+///     assert_eq!(CStr::from_ptr(formatted[0].name).to_str(), Ok("Plugin_ShowSomething"));
+/// }
+/// # }
+/// ```
 #[macro_export]
 macro_rules! natives {
+    [ $( { $name:literal, $func:ident } ),* ] => {
+        natives![ $( $name => $func ),* ]
+    };
+
     [ $( { $name:expr, $func:ident } ),* ] => {
-        {
-            let natives = vec![
-                $(
-                    $crate::types::AMX_NATIVE_INFO {
-                        name: ::std::ffi::CString::new($name).unwrap().into_raw(),
-                        func: $func,
-                    }
-                ),*
-            ];
-
-            natives
-        }
+        natives![ $( $name => $func ),* ]
+    };
+
+    [ $( $name:literal => $func:ident ),* ] => {
+        $crate::amx::Natives::new(vec![
+            $(
+                (
+                    ::std::borrow::Cow::Borrowed(::std::ffi::CStr::from_bytes_with_nul(concat!($name, "\0").as_bytes()).unwrap()),
+                    $func as $crate::types::AmxNative,
+                )
+            ),*
+        ])
     };
 
     [ $( $name:expr => $func:ident ),* ] => {
-        {
-            let natives = vec![
-                $(
-                    $crate::types::AMX_NATIVE_INFO {
-                        name: ::std::ffi::CString::new($name).unwrap().into_raw(),
-                        func: $func,
-                    }
-                ),*
-            ];
-
-            natives
-        }
+        $crate::amx::Natives::new(vec![
+            $(
+                (
+                    ::std::borrow::Cow::Owned(::std::ffi::CString::new($name).unwrap()),
+                    $func as $crate::types::AmxNative,
+                )
+            ),*
+        ])
     };
 }
 
 /// Hides ugly C code from your eyes.
 ///
 /// Generates raw extern C functions and makes call to your own static methods.
+/// Panics in these methods are caught and logged, so they don't crash the server.
 ///
 /// # Examples
 ///
@@ -115,6 +142,52 @@ macro_rules! natives {
 ///
 /// new_plugin!(MyPlugin with process_tick);
 /// ```
+///
+/// By default a plugin tells the server that it supports natives.
+/// Flags returned by `Supports()` can be set explicitly: `version`, `amx_natives` and `process_tick`.
+/// A plugin that only uses `ProcessTick` and doesn't register natives:
+/// ```
+/// #[macro_use] extern crate samp_sdk;
+/// use samp_sdk::amx::AMX;
+/// use samp_sdk::types;
+///
+/// struct MyPlugin;
+///
+/// impl MyPlugin {
+///     fn process_tick(&self) {}
+///
+///     fn load(&self) -> bool { true }
+///     fn unload(&self) {}
+///     fn amx_load(&self, amx: &AMX) -> types::Cell { 0 }
+///     fn amx_unload(&self, amx: &AMX) -> types::Cell { 0 }
+/// }
+///
+/// impl Default for MyPlugin {
+///     fn default() -> MyPlugin {
+///         MyPlugin {}
+///     }
+/// }
+///
+/// new_plugin!(MyPlugin with process_tick, supports(version));
+/// ```
+///
+/// Unknown flags are rejected at compile time:
+/// ```compile_fail
+/// # #[macro_use] extern crate samp_sdk;
+/// # use samp_sdk::amx::AMX;
+/// # use samp_sdk::types;
+/// # struct MyPlugin;
+/// # impl MyPlugin {
+/// #     fn load(&self) -> bool { true }
+/// #     fn unload(&self) {}
+/// #     fn amx_load(&self, amx: &AMX) -> types::Cell { 0 }
+/// #     fn amx_unload(&self, amx: &AMX) -> types::Cell { 0 }
+/// # }
+/// # impl Default for MyPlugin {
+/// #     fn default() -> MyPlugin { MyPlugin {} }
+/// # }
+/// new_plugin!(MyPlugin with supports(version | amx_natives | unknown_flag));
+/// ```
 #[macro_export]
 macro_rules! new_plugin {
     (@internal $name:ident) => {
@@ -126,32 +199,40 @@ macro_rules! new_plugin {
 
         #[no_mangle]
         pub unsafe extern "system" fn Load(data: *const *const u32) -> bool {
-            let mut log = $crate::data::logprintf.lock().unwrap();
-
-            ___PLUGIN = Box::into_raw(Box::new($name::default()));
+            $crate::data::refresh(data);
 
-            *log = *(data as *const $crate::types::Logprintf_t);
-            $crate::data::amx_functions = std::ptr::read(data.offset($crate::consts::PLUGIN_DATA_AMX_EXPORTS as isize) as *const *const u32);
-
-            drop(log);
-            (*___PLUGIN).load()
+            $crate::macros::catch_panic("Load", false, || {
+                ___PLUGIN = Box::into_raw(Box::new($name::default()));
+                (*___PLUGIN).load()
+            })
         }
 
         #[no_mangle]
         pub unsafe extern "system" fn Unload() {
-            (*___PLUGIN).unload();
+            $crate::macros::catch_panic("Unload", (), || {
+                (*___PLUGIN).unload();
+            })
         }
 
         #[no_mangle]
         pub unsafe extern "system" fn AmxLoad(amx: *mut $crate::types::AMX) -> $crate::types::Cell {
-            let mut amx = $crate::amx::AMX::new(amx);
-            (*___PLUGIN).amx_load(&mut amx)
+            $crate::amx::add(amx);
+
+            $crate::macros::catch_panic("AmxLoad", 0, || {
+                let mut amx = $crate::amx::AMX::new(amx);
+                (*___PLUGIN).amx_load(&mut amx)
+            })
         }
 
         #[no_mangle]
         pub unsafe extern "system" fn AmxUnload(amx: *mut $crate::types::AMX) -> $crate::types::Cell {
-            let mut amx = $crate::amx::AMX::new(amx);
-            (*___PLUGIN).amx_unload(&mut amx)
+            let retval = $crate::macros::catch_panic("AmxUnload", 0, || {
+                let mut amx = $crate::amx::AMX::new(amx);
+                (*___PLUGIN).amx_unload(&mut amx)
+            });
+
+            $crate::amx::remove(amx);
+            retval
         }
     };
 
@@ -169,14 +250,45 @@ macro_rules! new_plugin {
 
         #[no_mangle]
         pub unsafe extern "system" fn ProcessTick() {
-            (*___PLUGIN).process_tick();
+            $crate::macros::catch_panic("ProcessTick", (), || {
+                (*___PLUGIN).process_tick();
+            })
         }
 
         #[no_mangle]
         pub extern "system" fn Supports() -> u32 {
             $crate::consts::SUPPORTS_PROCESS_TICK | $crate::consts::SUPPORTS_VERSION | $crate::consts::SUPPORTS_AMX_NATIVES
         }
-    }
+    };
+
+    (@flag version) => { $crate::consts::SUPPORTS_VERSION };
+    (@flag amx_natives) => { $crate::consts::SUPPORTS_AMX_NATIVES };
+    (@flag process_tick) => { $crate::consts::SUPPORTS_PROCESS_TICK };
+
+    ($name:ident with supports($( $flag:ident )|+)) => {
+        new_plugin!(@internal $name);
+
+        #[no_mangle]
+        pub extern "system" fn Supports() -> u32 {
+            0 $( | new_plugin!(@flag $flag) )+
+        }
+    };
+
+    ($name:ident with process_tick, supports($( $flag:ident )|+)) => {
+        new_plugin!(@internal $name);
+
+        #[no_mangle]
+        pub unsafe extern "system" fn ProcessTick() {
+            $crate::macros::catch_panic("ProcessTick", (), || {
+                (*___PLUGIN).process_tick();
+            })
+        }
+
+        #[no_mangle]
+        pub extern "system" fn Supports() -> u32 {
+            $crate::consts::SUPPORTS_PROCESS_TICK $( | new_plugin!(@flag $flag) )+
+        }
+    };
 }
 
 /// Useful macro to log to SA:MP server output.
@@ -185,9 +297,10 @@ macro_rules! new_plugin {
 #[macro_export]
 macro_rules! log {
     ($( $arg:tt )* ) => {
-        {
-            let printf = $crate::data::logprintf.lock().unwrap();
-            let c_text = ::std::ffi::CString::new(format!($( $arg )*)).unwrap();
+        // Skipped if the lock is poisoned, e.g. when logging from a panic handler.
+        if let Ok(printf) = $crate::data::logprintf.lock() {
+            // Null bytes are removed, so the message is never lost.
+            let c_text = ::std::ffi::CString::new(format!($( $arg )*).replace('\0', "")).unwrap();
             printf(c_text.as_ptr());
         }
     }
@@ -195,7 +308,52 @@ macro_rules! log {
 
 /// Define native and hide raw C export functions.
 ///
+/// If a script passes fewer arguments than the native requires,
+/// the native logs it and raises `AmxError::Params` instead of reading past them.
+/// A panic in a native doesn't unwind into the server: it's logged and the native returns 0.
+///
 /// # Examples
+/// A panicking native returns 0, the panic is logged.
+/// ```
+/// #[macro_use] extern crate samp_sdk;
+/// use samp_sdk::amx::{AMX, AmxResult};
+/// use samp_sdk::testing::{self, TestAmx};
+/// use samp_sdk::types::Cell;
+///
+/// struct MyPlugin;
+///
+/// impl MyPlugin {
+///     fn load(&self) -> bool { true }
+///     fn unload(&self) {}
+///     fn amx_load(&self, amx: &AMX) -> Cell { 0 }
+///     fn amx_unload(&self, amx: &AMX) -> Cell { 0 }
+///
+///     fn divide(&self, _: &AMX, a: i32, b: i32) -> AmxResult<Cell> {
+///         Ok(a / b)
+///     }
+/// }
+///
+/// impl Default for MyPlugin {
+///     fn default() -> MyPlugin {
+///         MyPlugin {}
+///     }
+/// }
+///
+/// new_plugin!(MyPlugin);
+///
+/// // native: Divide(a, b);
+/// define_native!(divide, a: i32, b: i32);
+///
+/// # fn main() {
+/// unsafe { Load(testing::plugin_data().as_ptr()) };
+/// let mut test = TestAmx::new(&[], 16);
+///
+/// assert_eq!(test.call_native(divide, &[10, 2]), 5);
+/// assert_eq!(test.call_native(divide, &[10, 0]), 0);
+/// assert_eq!(test.take_log(), vec!["divide panicked: attempt to divide by zero"]);
+/// # }
+/// ```
+///
 /// Define a native with raw params (`*mut Cell`).
 /// ```compile_fail
 /// #[macro_use] extern crate samp_sdk;
@@ -208,6 +366,50 @@ macro_rules! log {
 /// fn with_raw_params(amx: &AMX, args: *mut Cell) -> AmxResult<Cell> { Ok(0) };
 /// ```
 ///
+/// A raw native can declare how many arguments it needs at least, which is checked the same way.
+/// `args::Parser::arg_count` tells how many were actually passed.
+/// ```
+/// # #[macro_use] extern crate samp_sdk;
+/// # use samp_sdk::amx::{AMX, AmxError, AmxResult};
+/// # use samp_sdk::testing::{self, TestAmx};
+/// # use samp_sdk::types::Cell;
+/// #
+/// # struct MyPlugin;
+/// #
+/// impl MyPlugin {
+/// #   fn load(&self) -> bool { true }
+/// #   fn unload(&self) {}
+/// #   fn amx_load(&self, amx: &AMX) -> Cell { 0 }
+/// #   fn amx_unload(&self, amx: &AMX) -> Cell { 0 }
+/// #
+///     fn format(&self, _: &AMX, _: *mut Cell) -> AmxResult<Cell> {
+///         Ok(1)
+///     }
+/// }
+/// #
+/// # impl Default for MyPlugin {
+/// #     fn default() -> MyPlugin {
+/// #         MyPlugin {}
+/// #     }
+/// # }
+/// #
+/// # new_plugin!(MyPlugin);
+/// #
+/// // native: Format(output[], len, const format[], {Float, _}:...);
+/// define_native!(format as raw, min_args = 3);
+///
+/// # fn main() {
+/// unsafe { Load(testing::plugin_data().as_ptr()) };
+/// let mut test = TestAmx::new(&[0; 4], 16);
+///
+/// assert_eq!(test.call_native(format, &[0, 4, 0, 10]), 1);
+/// assert!(test.take_error().is_none());
+///
+/// assert_eq!(test.call_native(format, &[0, 4]), 0);
+/// assert!(matches!(test.take_error(), Some(AmxError::Params)));
+/// # }
+/// ```
+///
 /// Define a native without arguments.
 /// ```compile_fail
 /// // native: WithoutArguments();
@@ -223,61 +425,473 @@ macro_rules! log {
 ///
 /// fn some_function(&self, amx: &AMX, int_val: &mut i32, float_val: f32) -> AmxResult<Cell>;
 /// ```
+///
+/// Strings can be taken as `CString` with raw bytes or as `String` decoded from cp1251.
+/// ```
+/// #[macro_use] extern crate samp_sdk;
+/// use samp_sdk::amx::{AMX, AmxResult};
+/// use samp_sdk::types::Cell;
+///
+/// struct MyPlugin;
+///
+/// impl MyPlugin {
+///     fn load(&self) -> bool { true }
+///     fn unload(&self) {}
+///     fn amx_load(&self, amx: &AMX) -> Cell { 0 }
+///     fn amx_unload(&self, amx: &AMX) -> Cell { 0 }
+///
+///     fn string_length(&self, _: &AMX, string: String) -> AmxResult<Cell> {
+///         Ok(string.chars().count() as Cell)
+///     }
+/// }
+///
+/// impl Default for MyPlugin {
+///     fn default() -> MyPlugin {
+///         MyPlugin {}
+///     }
+/// }
+///
+/// new_plugin!(MyPlugin);
+///
+/// // native: StringLength(const string[]);
+/// define_native!(string_length, string: String);
+/// # fn main() {}
+/// ```
+///
+/// An array followed by its size is taken as a mutable slice of AMX memory,
+/// so values written to it are seen by the script.
+/// ```
+/// # #[macro_use] extern crate samp_sdk;
+/// # use samp_sdk::amx::{AMX, AmxError, AmxResult};
+/// # use samp_sdk::testing::{self, TestAmx};
+/// # use samp_sdk::types::Cell;
+/// #
+/// # struct MyPlugin;
+/// #
+/// impl MyPlugin {
+/// #   fn load(&self) -> bool { true }
+/// #   fn unload(&self) {}
+/// #   fn amx_load(&self, amx: &AMX) -> Cell { 0 }
+/// #   fn amx_unload(&self, amx: &AMX) -> Cell { 0 }
+/// #
+///     fn get_player_weapons(&self, _: &AMX, player_id: i32, weapons: &mut [i32]) -> AmxResult<Cell> {
+///         for (slot, weapon) in weapons.iter_mut().enumerate() {
+///             *weapon = player_id * 10 + slot as i32;
+///         }
+///
+///         Ok(weapons.len() as Cell)
+///     }
+/// }
+/// #
+/// # impl Default for MyPlugin {
+/// #     fn default() -> MyPlugin {
+/// #         MyPlugin {}
+/// #     }
+/// # }
+/// #
+/// # new_plugin!(MyPlugin);
+/// #
+/// // native: GetPlayerWeapons(playerid, weapons[], size = sizeof(weapons));
+/// define_native!(get_player_weapons, player_id: i32, weapons: array i32);
+///
+/// # fn main() {
+/// unsafe { Load(testing::plugin_data().as_ptr()) };
+/// // `weapons` is at the address 0.
+/// let mut test = TestAmx::new(&[0; 3], 16);
+///
+/// assert_eq!(test.call_native(get_player_weapons, &[2, 0, 3]), 3);
+/// assert_eq!(test.memory()[..3], [20, 21, 22]);
+/// # }
+/// ```
+///
+/// Trailing arguments marked as `opt` are `None` if the script has passed fewer arguments.
+/// ```
+/// # #[macro_use] extern crate samp_sdk;
+/// # use samp_sdk::amx::{AMX, AmxError, AmxResult};
+/// # use samp_sdk::testing::{self, TestAmx};
+/// # use samp_sdk::types::Cell;
+/// #
+/// # struct MyPlugin;
+/// #
+/// impl MyPlugin {
+/// #   fn load(&self) -> bool { true }
+/// #   fn unload(&self) {}
+/// #   fn amx_load(&self, amx: &AMX) -> Cell { 0 }
+/// #   fn amx_unload(&self, amx: &AMX) -> Cell { 0 }
+/// #
+///     fn greet(&self, _: &AMX, name: String, title: Option<String>) -> AmxResult<Cell> {
+///         match title {
+///             Some(title) => log!("Hello, {} {}!", title, name),
+///             None => log!("Hello, {}!", name),
+///         }
+///
+///         Ok(1)
+///     }
+/// }
+/// #
+/// # impl Default for MyPlugin {
+/// #     fn default() -> MyPlugin {
+/// #         MyPlugin {}
+/// #     }
+/// # }
+/// #
+/// # new_plugin!(MyPlugin);
+/// #
+/// // native: Greet(const name[], const title[] = "");
+/// define_native!(greet, name: String, title: opt String);
+///
+/// # fn main() {
+/// unsafe { Load(testing::plugin_data().as_ptr()) };
+/// // "Bob" is at the address 0, "Sir" follows it at 16.
+/// let mut data = testing::string("Bob");
+/// data.extend(testing::string("Sir"));
+/// let mut test = TestAmx::new(&data, 16);
+///
+/// assert_eq!(test.call_native(greet, &[0]), 1);
+/// assert_eq!(test.call_native(greet, &[0, 16]), 1);
+/// assert_eq!(test.take_log(), vec!["Hello, Bob!", "Hello, Sir Bob!"]);
+/// # }
+/// ```
+///
+/// An `opt ref` argument is also `None` if the script passes the address 0,
+/// which is the usual way to say "don't write it".
+/// ```
+/// # #[macro_use] extern crate samp_sdk;
+/// # use samp_sdk::amx::{AMX, AmxError, AmxResult};
+/// # use samp_sdk::testing::{self, TestAmx};
+/// # use samp_sdk::types::Cell;
+/// #
+/// # struct MyPlugin;
+/// #
+/// impl MyPlugin {
+/// #   fn load(&self) -> bool { true }
+/// #   fn unload(&self) {}
+/// #   fn amx_load(&self, amx: &AMX) -> Cell { 0 }
+/// #   fn amx_unload(&self, amx: &AMX) -> Cell { 0 }
+/// #
+///     fn get_player_pos(&self, _: &AMX, _: i32, x: Option<&mut f32>, y: Option<&mut f32>, z: Option<&mut f32>) -> AmxResult<Cell> {
+///         let coords = vec![x, y, z];
+///         let written = coords.iter().filter(|coord| coord.is_some()).count();
+///
+///         for (coord, value) in coords.into_iter().zip(vec![1.5, 2.5, 3.5]) {
+///             if let Some(coord) = coord {
+///                 *coord = value;
+///             }
+///         }
+///
+///         Ok(written as Cell)
+///     }
+/// }
+/// #
+/// # impl Default for MyPlugin {
+/// #     fn default() -> MyPlugin {
+/// #         MyPlugin {}
+/// #     }
+/// # }
+/// #
+/// # new_plugin!(MyPlugin);
+/// #
+/// // native: GetPlayerPos(playerid, &Float:x = 0.0, &Float:y = 0.0, &Float:z = 0.0);
+/// define_native!(get_player_pos, player_id: i32, x: opt ref f32, y: opt ref f32, z: opt ref f32);
+///
+/// # fn main() {
+/// unsafe { Load(testing::plugin_data().as_ptr()) };
+/// // `x` and `y` are at the addresses 4 and 8, `z` is skipped.
+/// let mut test = TestAmx::new(&[0; 3], 16);
+///
+/// assert_eq!(test.call_native(get_player_pos, &[0, 4, 8, 0]), 2);
+/// assert_eq!(f32::from_bits(test.memory()[1] as u32), 1.5);
+/// assert_eq!(f32::from_bits(test.memory()[2] as u32), 2.5);
+///
+/// // Only `x` is passed.
+/// assert_eq!(test.call_native(get_player_pos, &[0, 4]), 1);
+/// # }
+/// ```
+///
+/// An `opt array` is `None` if the script hasn't passed it, but it can't be passed without its size.
+/// ```
+/// # #[macro_use] extern crate samp_sdk;
+/// # use samp_sdk::amx::{AMX, AmxError, AmxResult};
+/// # use samp_sdk::testing::{self, TestAmx};
+/// # use samp_sdk::types::Cell;
+/// #
+/// # struct MyPlugin;
+/// #
+/// impl MyPlugin {
+/// #   fn load(&self) -> bool { true }
+/// #   fn unload(&self) {}
+/// #   fn amx_load(&self, amx: &AMX) -> Cell { 0 }
+/// #   fn amx_unload(&self, amx: &AMX) -> Cell { 0 }
+/// #
+///     fn count_weapons(&self, _: &AMX, _: i32, weapons: Option<&mut [i32]>) -> AmxResult<Cell> {
+///         Ok(weapons.map_or(0, |weapons| weapons.iter().filter(|&&weapon| weapon != 0).count() as Cell))
+///     }
+/// }
+/// #
+/// # impl Default for MyPlugin {
+/// #     fn default() -> MyPlugin {
+/// #         MyPlugin {}
+/// #     }
+/// # }
+/// #
+/// # new_plugin!(MyPlugin);
+/// #
+/// // native: CountWeapons(playerid, const weapons[] = {0}, size = sizeof weapons);
+/// define_native!(count_weapons, player_id: i32, weapons: opt array i32);
+///
+/// # fn main() {
+/// unsafe { Load(testing::plugin_data().as_ptr()) };
+/// // `weapons` is at the address 0.
+/// let mut test = TestAmx::new(&[24, 0, 31], 16);
+///
+/// assert_eq!(test.call_native(count_weapons, &[0, 0, 3]), 2);
+/// assert_eq!(test.call_native(count_weapons, &[0]), 0);
+/// # assert!(test.take_error().is_none());
+/// #
+/// # // The array without its size.
+/// # assert_eq!(test.call_native(count_weapons, &[0, 0]), 0);
+/// # assert!(matches!(test.take_error(), Some(AmxError::Params)));
+/// # }
+/// ```
+///
+/// Only trailing arguments can be `opt`, a required argument after an `opt` one doesn't compile.
+/// ```compile_fail
+/// # #[macro_use] extern crate samp_sdk;
+/// # use samp_sdk::amx::{AMX, AmxResult};
+/// # use samp_sdk::types::Cell;
+/// #
+/// # struct MyPlugin;
+/// #
+/// # impl MyPlugin {
+/// #   fn load(&self) -> bool { true }
+/// #   fn unload(&self) {}
+/// #   fn amx_load(&self, amx: &AMX) -> Cell { 0 }
+/// #   fn amx_unload(&self, amx: &AMX) -> Cell { 0 }
+/// #   fn set_player_skin(&self, _: &AMX, _: Option<i32>, _: i32) -> AmxResult<Cell> { Ok(1) }
+/// # }
+/// #
+/// # impl Default for MyPlugin {
+/// #     fn default() -> MyPlugin {
+/// #         MyPlugin {}
+/// #     }
+/// # }
+/// #
+/// # new_plugin!(MyPlugin);
+/// #
+/// // native: SetPlayerSkin(playerid = -1, skin);
+/// define_native!(set_player_skin, player_id: opt i32, skin: i32);
+/// # fn main() {}
+/// ```
+///
+/// A `Float:` argument can also be taken as `f64`. The cell still holds a 32-bit float,
+/// so it is only widened: the extra precision is not coming from the script.
+/// A native returns a `Float:` value as `Float`, which is made from `f64` by narrowing it back.
+/// ```
+/// # #[macro_use] extern crate samp_sdk;
+/// # use samp_sdk::amx::{AMX, AmxError, AmxResult};
+/// # use samp_sdk::testing::{self, TestAmx};
+/// # use samp_sdk::types::{Cell, Float};
+/// #
+/// # struct MyPlugin;
+/// #
+/// impl MyPlugin {
+/// #   fn load(&self) -> bool { true }
+/// #   fn unload(&self) {}
+/// #   fn amx_load(&self, amx: &AMX) -> Cell { 0 }
+/// #   fn amx_unload(&self, amx: &AMX) -> Cell { 0 }
+/// #
+///     fn distance(&self, _: &AMX, x: f64, y: f64) -> AmxResult<Float> {
+///         Ok(Float::from((x * x + y * y).sqrt()))
+///     }
+/// }
+/// #
+/// # impl Default for MyPlugin {
+/// #     fn default() -> MyPlugin {
+/// #         MyPlugin {}
+/// #     }
+/// # }
+/// #
+/// # new_plugin!(MyPlugin);
+/// #
+/// // native: Distance(Float:x, Float:y);
+/// define_native!(distance, x: f64, y: f64);
+///
+/// # fn main() {
+/// unsafe { Load(testing::plugin_data().as_ptr()) };
+/// let mut test = TestAmx::new(&[], 16);
+/// let x = 3.0f32.to_bits() as Cell;
+/// let y = 4.0f32.to_bits() as Cell;
+///
+/// assert_eq!(test.call_native(distance, &[x, y]), Cell::from(Float(5.0)));
+/// # }
+/// ```
 #[macro_export]
 macro_rules! define_native {
     ($name:ident as raw) => {
         pub extern "C" fn $name(amx: *mut $crate::types::AMX, params: *mut $crate::types::Cell) -> $crate::types::Cell {
-            let mut amx = $crate::amx::AMX::new(amx);
-            match unsafe { (*crate::___PLUGIN).$name(&mut amx, params) } {
-                Ok(res) => return res,
-                Err(err) => {
-                    amx.raise_error(err).unwrap();
+            $crate::macros::catch_panic(stringify!($name), 0, || {
+                let mut amx = $crate::amx::AMX::new(amx);
+                match unsafe { (*crate::___PLUGIN).$name(&mut amx, params) } {
+                    Ok(res) => return $crate::types::Cell::from(res),
+                    Err(err) => {
+                        let _ = amx.raise_error(err);
+                        return 0;
+                    },
+                };
+            })
+        }
+    };
+
+    ($name:ident as raw, min_args = $min:expr) => {
+        pub extern "C" fn $name(amx: *mut $crate::types::AMX, params: *mut $crate::types::Cell) -> $crate::types::Cell {
+            $crate::macros::catch_panic(stringify!($name), 0, || {
+                let mut amx = $crate::amx::AMX::new(amx);
+
+                let required: usize = $min;
+                let passed = args_count!(params);
+
+                if passed < required {
+                    log!("{}: expected at least {} arguments, got {}", stringify!($name), required, passed);
+                    let _ = amx.raise_error($crate::amx::AmxError::Params);
                     return 0;
-                },
-            };
+                }
+
+                match unsafe { (*crate::___PLUGIN).$name(&mut amx, params) } {
+                    Ok(res) => return $crate::types::Cell::from(res),
+                    Err(err) => {
+                        let _ = amx.raise_error(err);
+                        return 0;
+                    },
+                };
+            })
         }
     };
 
     ($name:ident) => {
         pub extern "C" fn $name(amx: *mut $crate::types::AMX, _: *mut $crate::types::Cell) -> $crate::types::Cell {
-            let mut amx = $crate::amx::AMX::new(amx);
-            match unsafe { (*crate::___PLUGIN).$name(&mut amx) } {
-                Ok(res) => return res,
-                Err(err) => {
-                    amx.raise_error(err).unwrap();
-                    return 0;
-                },
-            };
+            $crate::macros::catch_panic(stringify!($name), 0, || {
+                let mut amx = $crate::amx::AMX::new(amx);
+                match unsafe { (*crate::___PLUGIN).$name(&mut amx) } {
+                    Ok(res) => return $crate::types::Cell::from(res),
+                    Err(err) => {
+                        let _ = amx.raise_error(err);
+                        return 0;
+                    },
+                };
+            })
         }
     };
 
     ($name:ident, $( $arg:ident : $( $data:ident )+ ),* ) => {
         pub extern "C" fn $name(amx: *mut $crate::types::AMX, params: *mut $crate::types::Cell) -> $crate::types::Cell {
-            let mut amx = $crate::amx::AMX::new(amx);
-            expand_args!(amx, params, $( $arg : $( $data )+ ),* );
+            $crate::macros::catch_panic(stringify!($name), 0, || {
+                let mut amx = $crate::amx::AMX::new(amx);
 
-            let retval = unsafe {
-                (*crate::___PLUGIN).$name(&mut amx, $($arg),*)
-            };
+                let required = expand_args!(@required $( $arg : $( $data )+ ),*);
+                let passed = args_count!(params);
 
-            match retval {
-                Ok(res) => return res,
-                Err(err) => {
-                    amx.raise_error(err).unwrap();
+                if passed < required {
+                    log!("{}: expected {} arguments, got {}", stringify!($name), required, passed);
+                    let _ = amx.raise_error($crate::amx::AmxError::Params);
                     return 0;
-                },
-            };
+                }
+
+                expand_args!(amx, params => stringify!($name), $( $arg : $( $data )+ ),* );
+
+                let retval = unsafe {
+                    (*crate::___PLUGIN).$name(&mut amx, $($arg),*)
+                };
+
+                match retval {
+                    Ok(res) => return $crate::types::Cell::from(res),
+                    Err(err) => {
+                        let _ = amx.raise_error(err);
+                        return 0;
+                    },
+                };
+            })
         }
     }
 }
 
+/// Reads arguments of a native into variables, the way `define_native!` does.
+///
+/// Can be used in a native returning `Cell`: if an argument can't be read,
+/// the error is logged and raised in the script and the native returns 0.
+///
+/// A `ref` argument is a `&mut` reference right into AMX memory, so the script sees
+/// the new value after the native returns without any copying back.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use] extern crate samp_sdk;
+/// use samp_sdk::amx::AMX;
+/// use samp_sdk::testing::TestAmx;
+/// use samp_sdk::types::{self, Cell};
+///
+/// // native: GetPlayerStats(playerid, &score, &Float:health);
+/// extern "C" fn get_player_stats(amx: *mut types::AMX, params: *mut Cell) -> Cell {
+///     let amx = AMX::new(amx);
+///     expand_args!(amx, params, player_id: i32, score: ref i32, health: ref f32);
+///
+///     *score = player_id * 10;
+///     *health = 99.5;
+///
+///     1
+/// }
+///
+/// # fn main() {
+/// // The script's variables `score` and `health` live at addresses 0 and 4.
+/// let mut test = TestAmx::new(&[0, 0], 16);
+///
+/// assert_eq!(test.call_native(get_player_stats, &[7, 0, 4]), 1);
+/// assert_eq!(test.memory()[0], 70);
+/// assert_eq!(f32::from_bits(test.memory()[1] as u32), 99.5);
+/// # }
+/// ```
 #[macro_export]
 macro_rules! expand_args {
+    // Count of cells required by arguments.
+    (@required) => { 0 };
+
+    (@required $arg:ident : opt $( $data:ident )+ $( , $( $tail:tt )* )? ) => {
+        expand_args!(@optional $( $( $tail )* )?)
+    };
+
+    (@required $arg:ident : array $type:ident $( , $( $tail:tt )* )? ) => {
+        2 + expand_args!(@required $( $( $tail )* )?)
+    };
+
+    (@required $arg:ident : $( $data:ident )+ $( , $( $tail:tt )* )? ) => {
+        1 + expand_args!(@required $( $( $tail )* )?)
+    };
+
+    // Arguments after an `opt` one, which have to be optional too.
+    (@optional) => { 0 };
+
+    (@optional $arg:ident : opt $( $data:ident )+ $( , $( $tail:tt )* )? ) => {
+        expand_args!(@optional $( $( $tail )* )?)
+    };
+
+    (@optional $arg:ident : $( $data:ident )+ $( , $( $tail:tt )* )? ) => {
+        compile_error!(concat!("argument `", stringify!($arg), "` follows an `opt` argument, so it has to be `opt` too"))
+    };
+
+    // Logs and raises an error of an argument, then leaves the native.
+    (@fail $amx:ident, $native:expr, $arg:ident, $err:expr) => {{
+        let err = $err;
+        log!("{}: can't get argument {}: {}", $native, stringify!($arg), err);
+        let _ = $amx.raise_error(err);
+        return 0;
+    }};
+
     // A string.
     (
         @
         $amx:ident,
         $parser:ident,
+        $native:expr,
 
         $arg:ident : CString
     ) => {
@@ -285,10 +899,25 @@ macro_rules! expand_args {
             let arg = $parser.next();
             match $amx.get_cstring(arg) {
                 Ok(res) => res,
-                Err(err) => {
-                    $amx.raise_error(err).unwrap();
-                    return 0;
-                },
+                Err(err) => expand_args!(@fail $amx, $native, $arg, err),
+            }
+        };
+    };
+
+    // A string decoded from cp1251.
+    (
+        @
+        $amx:ident,
+        $parser:ident,
+        $native:expr,
+
+        $arg:ident : String
+    ) => {
+        let $arg = {
+            let arg = $parser.next();
+            match $amx.get_cstring(arg).and_then(|string| $crate::cp1251::decode(string.as_bytes())) {
+                Ok(res) => res,
+                Err(err) => expand_args!(@fail $amx, $native, $arg, err),
             }
         };
     };
@@ -298,6 +927,7 @@ macro_rules! expand_args {
         @
         $amx:ident,
         $parser:ident,
+        $native:expr,
 
         $arg:ident : ref $type:ty
     ) => {
@@ -305,19 +935,126 @@ macro_rules! expand_args {
             let ptr = $parser.next();
             match $amx.get_address(::std::ptr::read(ptr as *const $crate::types::Cell)) {
                 Ok(res) => res,
-                Err(err) => {
-                    $amx.raise_error(err).unwrap();
-                    return 0;
-                },
+                Err(err) => expand_args!(@fail $amx, $native, $arg, err),
+            }
+        };
+    };
+
+    // An optional reference, `None` if it's not passed or its address is 0.
+    (
+        @
+        $amx:ident,
+        $parser:ident,
+        $native:expr,
+
+        $arg:ident : opt ref $type:ty
+    ) => {
+        let $arg: Option<&mut $type> = if $parser.has_next() {
+            let address = unsafe { ::std::ptr::read($parser.next()) };
+
+            if address == 0 {
+                None
+            } else {
+                match $amx.get_address(address) {
+                    Ok(res) => Some(res),
+                    Err(err) => expand_args!(@fail $amx, $native, $arg, err),
+                }
+            }
+        } else {
+            None
+        };
+    };
+
+    // An optional array, `None` if it's not passed. Its size has to be passed too.
+    (
+        @
+        $amx:ident,
+        $parser:ident,
+        $native:expr,
+
+        $arg:ident : opt array $type:ty
+    ) => {
+        let $arg: Option<&mut [$type]> = match $parser.remaining() {
+            0 => None,
+            1 => expand_args!(@fail $amx, $native, $arg, $crate::amx::AmxError::Params),
+            _ => {
+                expand_args!(@$amx, $parser, $native, $arg : array $type);
+                Some($arg)
+            },
+        };
+    };
+
+    // An optional argument.
+    (
+        @
+        $amx:ident,
+        $parser:ident,
+        $native:expr,
+
+        $arg:ident : opt $( $data:ident )+
+    ) => {
+        let $arg = if $parser.has_next() {
+            expand_args!(@$amx, $parser, $native, $arg : $( $data )+);
+            Some($arg)
+        } else {
+            None
+        };
+    };
+
+    // An array followed by its size.
+    (
+        @
+        $amx:ident,
+        $parser:ident,
+        $native:expr,
+
+        $arg:ident : array $type:ty
+    ) => {
+        let $arg: &mut [$type] = unsafe {
+            let address = ::std::ptr::read($parser.next());
+            let size: $crate::types::Cell = ::std::ptr::read($parser.next());
+
+            let array = if size < 0 {
+                Err($crate::amx::AmxError::Params)
+            } else {
+                let last = (size - 1).max(0)
+                    .checked_mul(::std::mem::size_of::<$crate::types::Cell>() as $crate::types::Cell)
+                    .and_then(|offset| address.checked_add(offset))
+                    .unwrap_or(-1);
+
+                $amx.get_address::<$type>(last)
+                    .and_then(|_| $amx.get_address::<$type>(address))
+                    .map(|first| ::std::slice::from_raw_parts_mut(first, size as usize))
+            };
+
+            match array {
+                Ok(res) => res,
+                Err(err) => expand_args!(@fail $amx, $native, $arg, err),
             }
         };
     };
 
+    // A Float: value widened to f64.
+    (
+        @
+        $amx:ident,
+        $parser:ident,
+        $native:expr,
+
+        $arg:ident : f64
+    ) => {
+        let $arg: f64 = unsafe {
+            let ptr = $parser.next();
+            f64::from($crate::types::Float::from(::std::ptr::read(ptr)))
+        };
+    };
+
     // An primitive value.
     (
         @
         $amx:ident,
         $parser:ident,
+        $native:expr,
 
         $arg:ident : $type:ty
     ) => {
@@ -331,46 +1068,110 @@ macro_rules! expand_args {
         @
         $amx:ident,
         $parser:ident,
+        $native:expr,
 
         $arg:ident : ref $type:ty,
         $( $tail_arg:ident : $( $tail_data:ident )+ ),*
     ) => {
-        expand_args!(@$amx, $parser, $arg : ref $type);
-        expand_args!(@$amx, $parser, $( $tail_arg : $( $tail_data )+ ),*);
+        expand_args!(@$amx, $parser, $native, $arg : ref $type);
+        expand_args!(@$amx, $parser, $native, $( $tail_arg : $( $tail_data )+ ),*);
     };
 
     (
         @
         $amx:ident,
         $parser:ident,
+        $native:expr,
+
+        $arg:ident : opt $( $data:ident )+,
+        $( $tail_arg:ident : $( $tail_data:ident )+ ),*
+    ) => {
+        expand_args!(@$amx, $parser, $native, $arg : opt $( $data )+);
+        expand_args!(@$amx, $parser, $native, $( $tail_arg : $( $tail_data )+ ),*);
+    };
+
+    (
+        @
+        $amx:ident,
+        $parser:ident,
+        $native:expr,
+
+        $arg:ident : array $type:ty,
+        $( $tail_arg:ident : $( $tail_data:ident )+ ),*
+    ) => {
+        expand_args!(@$amx, $parser, $native, $arg : array $type);
+        expand_args!(@$amx, $parser, $native, $( $tail_arg : $( $tail_data )+ ),*);
+    };
+
+    (
+        @
+        $amx:ident,
+        $parser:ident,
+        $native:expr,
 
         $arg:ident : CString,
         $( $tail_arg:ident : $( $tail_data:ident )+ ),*
     ) => {
-        expand_args!(@$amx, $parser, $arg : CString);
-        expand_args!(@$amx, $parser, $( $tail_arg : $( $tail_data )+ ),*);
+        expand_args!(@$amx, $parser, $native, $arg : CString);
+        expand_args!(@$amx, $parser, $native, $( $tail_arg : $( $tail_data )+ ),*);
     };
 
     (
         @
         $amx:ident,
         $parser:ident,
+        $native:expr,
+
+        $arg:ident : String,
+        $( $tail_arg:ident : $( $tail_data:ident )+ ),*
+    ) => {
+        expand_args!(@$amx, $parser, $native, $arg : String);
+        expand_args!(@$amx, $parser, $native, $( $tail_arg : $( $tail_data )+ ),*);
+    };
+
+    (
+        @
+        $amx:ident,
+        $parser:ident,
+        $native:expr,
+
+        $arg:ident : f64,
+        $( $tail_arg:ident : $( $tail_data:ident )+ ),*
+    ) => {
+        expand_args!(@$amx, $parser, $native, $arg : f64);
+        expand_args!(@$amx, $parser, $native, $( $tail_arg : $( $tail_data )+ ),*);
+    };
+
+    (
+        @
+        $amx:ident,
+        $parser:ident,
+        $native:expr,
 
         $arg:ident : $type:ty,
         $( $tail_arg:ident : $( $tail_data:ident )+ ),*
     ) => {
-        expand_args!(@$amx, $parser, $arg : $type);
-        expand_args!(@$amx, $parser, $( $tail_arg : $( $tail_data )+ ),*);
+        expand_args!(@$amx, $parser, $native, $arg : $type);
+        expand_args!(@$amx, $parser, $native, $( $tail_arg : $( $tail_data )+ ),*);
     };
 
     (
         $amx:ident,
         $params:ident,
 
+        $( $arg:ident : $($data:ident)+ ),*
+    ) => {
+        expand_args!($amx, $params => module_path!(), $( $arg : $( $data )+ ),*);
+    };
+
+    (
+        $amx:ident,
+        $params:ident => $native:expr,
+
         $( $arg:ident : $($data:ident)+ ),*
     ) => {
         let mut parser = $crate::args::Parser::new($params);
-        expand_args!(@$amx, parser, $( $arg : $( $data )+ ),*);
+        expand_args!(@$amx, parser, $native, $( $arg : $( $data )+ ),*);
     };
 }
 
@@ -707,4 +1508,23 @@ macro_rules! get_array {
         $amx.get_address($addr)
             .map(|pointer| unsafe { ::std::slice::from_raw_parts_mut(pointer, $len) })
     };
-}
\ No newline at end of file
+}
+
+/// Calls `function` and catches a panic, so it doesn't unwind into the server.
+///
+/// A panic is logged with `name` and `default` is returned instead.
+/// Used in functions made by `new_plugin!` and `define_native!`.
+#[doc(hidden)]
+pub fn catch_panic<R, F: FnOnce() -> R>(name: &str, default: R, function: F) -> R {
+    match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(function)) {
+        Ok(retval) => retval,
+        Err(payload) => {
+            let message = payload.downcast_ref::<&str>().map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_owned());
+
+            log!("{} panicked: {}", name, message);
+            default
+        },
+    }
+}
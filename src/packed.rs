@@ -0,0 +1,127 @@
+/*!
+    Packed strings.
+
+    A packed string keeps four characters in a cell. Pawn orders them as big-endian,
+    so the first character is in the most significant byte of a cell regardless of the platform.
+*/
+
+use std::mem::{size_of, size_of_val};
+
+use crate::types::Cell;
+
+/// The largest value of a character in an unpacked string.
+pub const UNPACKED_MAX: u32 = (1 << ((size_of::<Cell>() - 1) * 8)) - 1;
+
+/// Checks if a string which starts with `first` cell is packed.
+///
+/// A packed string has its first character in the high byte of the first cell, so the cell is above `UNPACKED_MAX`.
+/// An empty string can't be told apart and is considered unpacked.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::packed;
+/// use samp_sdk::types::Cell;
+///
+/// assert!(packed::is_packed(packed::pack(b"abc")[0]));
+/// assert!(!packed::is_packed(Cell::from(b'a')));
+/// assert!(!packed::is_packed(0));
+/// ```
+pub fn is_packed(first: Cell) -> bool {
+    first as u32 > UNPACKED_MAX
+}
+
+/// Packs bytes of a string into cells.
+///
+/// The result always ends with a null character, so it has `bytes.len() / 4 + 1` cells.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::packed;
+///
+/// assert_eq!(packed::pack(b"abc"), vec![0x61626300]);
+/// assert_eq!(packed::pack(b"abcd"), vec![0x61626364, 0]);
+/// assert_eq!(packed::pack(b"abcde"), vec![0x61626364, 0x65000000]);
+/// ```
+pub fn pack(bytes: &[u8]) -> Vec<Cell> {
+    let cells = bytes.len() / size_of::<Cell>() + 1;
+    let mut packed = Vec::with_capacity(cells);
+
+    for position in 0..cells {
+        let mut cell = [0u8; 4];
+
+        for (byte, character) in cell.iter_mut().zip(bytes.iter().skip(position * size_of::<Cell>())) {
+            *byte = *character;
+        }
+
+        packed.push(Cell::from_be_bytes(cell));
+    }
+
+    packed
+}
+
+/// Unpacks cells of a packed string into bytes.
+///
+/// Stops at the first null character, the result doesn't contain it.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::packed;
+///
+/// assert_eq!(packed::unpack(&[0x61626300]), b"abc");
+/// assert_eq!(packed::unpack(&[0x61626364, 0]), b"abcd");
+/// assert_eq!(packed::unpack(&[0x61626364, 0x65000000]), b"abcde");
+/// assert_eq!(packed::unpack(&[0x61626364]), b"abcd");
+/// ```
+pub fn unpack(cells: &[Cell]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(size_of_val(cells));
+    unpack_into(cells, &mut bytes);
+    bytes
+}
+
+/// Unpacks cells of a packed string to the end of `bytes`.
+///
+/// See `unpack`.
+pub fn unpack_into(cells: &[Cell], bytes: &mut Vec<u8>) {
+    let characters = cells.iter()
+        .flat_map(|cell| cell.to_be_bytes().to_vec())
+        .take_while(|&byte| byte != 0);
+
+    bytes.extend(characters);
+}
+
+/// Counts characters of a string in cells, packed or not, until the null character or the end of `cells`.
+///
+/// Unlike `AMX::string_len` it doesn't need the server, so it works on a copy of AMX memory.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::packed;
+///
+/// assert_eq!(packed::len(&[0]), 0);
+/// assert_eq!(packed::len(&[]), 0);
+///
+/// // Unpacked.
+/// assert_eq!(packed::len(&[0x61, 0x62, 0x63, 0]), 3);
+/// assert_eq!(packed::len(&[0x61, 0x62, 0x63, 0x64, 0]), 4);
+///
+/// // Packed.
+/// assert_eq!(packed::len(&packed::pack(b"abc")), 3);
+/// assert_eq!(packed::len(&packed::pack(b"abcd")), 4);
+/// assert_eq!(packed::len(&packed::pack(b"abcdefgh")), 8);
+/// assert_eq!(packed::len(&[0x61626364, 0x65666768]), 8);
+/// ```
+pub fn len(cells: &[Cell]) -> usize {
+    match cells.first() {
+        Some(&first) if is_packed(first) => cells.iter()
+            .flat_map(|cell| cell.to_be_bytes().to_vec())
+            .take_while(|&byte| byte != 0)
+            .count(),
+        _ => cells.iter()
+            .take_while(|&&cell| cell != 0)
+            .count(),
+    }
+}
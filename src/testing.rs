@@ -0,0 +1,1085 @@
+/*!
+    An AMX in Rust memory to test natives without a server.
+
+    `TestAmx` has a data section given by a test, an empty heap and a stack.
+    Its header has tables of publics, natives, public variables and tags, but there is no code:
+    publics are Rust closures added with `TestAmx::add_public`.
+
+    The AMX functions used by the SDK are implemented in Rust: `Align16`, `Align32`, `Allot`, `Release`,
+    `Push`, `GetAddr`, `StrLen`, `SetString`, `RaiseError`, `Exec`, `Clone`, `Flags`, `MemInfo`, `SetDebugHook`,
+    `UTF8Check`, `UTF8Get`, `UTF8Put`, `Register`, `FindPublic`, `FindNative`, `FindPubVar`, `FindTagId`,
+    `GetNative`, `GetTag` and the `Num*` ones. `Exec` calls the debug hook once before a public, as if the public
+    started with `break`, and fails with an error raised while the public runs.
+
+    The functions are installed into `data::amx_functions` when the first `TestAmx` is made,
+    unless the server has already given its own ones. `data::logprintf` is set the same way
+    to a function which keeps the output, so error paths that log can be tested too.
+
+    Available in the crate's own tests and with the `testing` feature.
+
+    # Examples
+
+    ```
+    #[macro_use] extern crate samp_sdk;
+    use samp_sdk::amx::AMX;
+    use samp_sdk::testing::TestAmx;
+    use samp_sdk::types::{self, Cell};
+
+    // native: CallScore(playerid, &score);
+    // Asks the script for a score of the player with `GetScore(playerid)`.
+    extern "C" fn call_score(amx: *mut types::AMX, params: *mut Cell) -> Cell {
+        let amx = AMX::new(amx);
+        expand_args!(amx, params, player_id: i32, score: ref i32);
+
+        let index = amx.find_public("GetScore").unwrap();
+        amx.push(player_id).unwrap();
+        *score = amx.exec(index).unwrap();
+
+        1
+    }
+
+    # fn main() {
+    // A variable for `score` at the address 0.
+    let mut test = TestAmx::new(&[0], 64);
+    test.add_public("GetScore", |_, args| args[0] * 100);
+
+    assert_eq!(test.call_native(call_score, &[7, 0]), 1);
+    assert_eq!(test.memory()[0], 700);
+    # }
+    ```
+
+    Strings are put in the data section, errors raised by a native are taken with `take_error`:
+
+    ```
+    #[macro_use] extern crate samp_sdk;
+    use samp_sdk::amx::{AMX, AmxError};
+    use samp_sdk::testing::{self, TestAmx};
+    use samp_sdk::types::{self, Cell};
+
+    // native: NameLength(const name[]);
+    extern "C" fn name_length(amx: *mut types::AMX, params: *mut Cell) -> Cell {
+        let amx = AMX::new(amx);
+        expand_args!(amx, params, name: String);
+
+        if name.is_empty() {
+            let _ = amx.raise_error(AmxError::Params);
+            return 0;
+        }
+
+        name.chars().count() as Cell
+    }
+
+    # fn main() {
+    let mut test = TestAmx::new(&testing::string("Bob"), 64);
+    assert_eq!(test.call_native(name_length, &[0]), 3);
+    assert!(test.take_error().is_none());
+
+    let mut test = TestAmx::new(&testing::string(""), 64);
+    assert_eq!(test.call_native(name_length, &[0]), 0);
+
+    match test.take_error() {
+        Some(AmxError::Params) => (),
+        _ => panic!("the name is empty"),
+    }
+    # }
+    ```
+*/
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::mem::{size_of, size_of_val, transmute, MaybeUninit};
+use std::os::raw::{c_char, c_void};
+use std::ptr::{self, null};
+use std::slice;
+use std::sync::{Arc, Mutex};
+
+use crate::amx::{AMX, AmxError};
+use crate::consts::*;
+use crate::data::{amx_functions, logprintf};
+use crate::lazy_static;
+use crate::packed;
+use crate::types::{self, AmxNative, Cell, Ucell, AMX_FUNCSTUB, FUNCSTUBNT};
+
+/// A public of a `TestAmx`, it gets arguments in the order the script passes them.
+pub type Public = Arc<dyn Fn(&AMX, &[Cell]) -> Cell + Send + Sync>;
+
+/// Cells kept free between the heap and the stack, like the server does.
+const STACK_MARGIN: Cell = 16 * size_of::<Cell>() as Cell;
+
+lazy_static! {
+    static ref publics: Mutex<HashMap<usize, Vec<Public>>> = Mutex::new(HashMap::new());
+
+    static ref output: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    static ref exports: Vec<usize> = {
+        let mut table = vec![0; Exports::UTF8Put as usize + 1];
+
+        table[Exports::Align16 as usize] = align16 as types::Align16 as usize;
+        table[Exports::Align32 as usize] = align32 as types::Align32 as usize;
+        table[Exports::Allot as usize] = allot as types::Allot as usize;
+        table[Exports::Clone as usize] = clone as types::Clone as usize;
+        table[Exports::Exec as usize] = exec as types::Exec as usize;
+        table[Exports::FindNative as usize] = find_native as types::FindNative as usize;
+        table[Exports::FindPublic as usize] = find_public as types::FindPublic as usize;
+        table[Exports::FindPubVar as usize] = find_pubvar as types::FindPubVar as usize;
+        table[Exports::FindTagId as usize] = find_tag_id as types::FindTagId as usize;
+        table[Exports::Flags as usize] = flags as types::Flags as usize;
+        table[Exports::GetAddr as usize] = get_addr as types::GetAddr as usize;
+        table[Exports::GetNative as usize] = get_native as types::GetNative as usize;
+        table[Exports::GetTag as usize] = get_tag as types::GetTag as usize;
+        table[Exports::MemInfo as usize] = mem_info as types::MemInfo as usize;
+        table[Exports::NumNatives as usize] = num_natives as types::NumNatives as usize;
+        table[Exports::NumPublics as usize] = num_publics as types::NumPublics as usize;
+        table[Exports::NumPubVars as usize] = num_pubvars as types::NumPubVars as usize;
+        table[Exports::NumTags as usize] = num_tags as types::NumTags as usize;
+        table[Exports::Push as usize] = push as types::Push as usize;
+        table[Exports::RaiseError as usize] = raise_error as types::RaiseError as usize;
+        table[Exports::Register as usize] = register as types::Register as usize;
+        table[Exports::Release as usize] = release as types::Release as usize;
+        table[Exports::SetDebugHook as usize] = set_debug_hook as types::SetDebugHook as usize;
+        table[Exports::SetString as usize] = set_string as types::SetString as usize;
+        table[Exports::StrLen as usize] = str_len as types::StrLen as usize;
+        table[Exports::UTF8Check as usize] = utf8_check as types::UTF8Check as usize;
+        table[Exports::UTF8Get as usize] = utf8_get as types::UTF8Get as usize;
+        table[Exports::UTF8Put as usize] = utf8_put as types::UTF8Put as usize;
+
+        table
+    };
+}
+
+/// Names and values of entries of the header tables.
+#[derive(Default)]
+struct Tables {
+    publics: Vec<CString>,
+    natives: Vec<(CString, Ucell)>,
+    pubvars: Vec<(CString, Ucell)>,
+    tags: Vec<(CString, Ucell)>,
+}
+
+/// An AMX with its header and memory in Rust memory.
+pub struct TestAmx {
+    raw: *mut types::AMX,
+    header: *mut [Cell],
+    memory: *mut [Cell],
+    tables: Tables,
+    inline_names: bool,
+}
+
+impl TestAmx {
+    /// Makes an AMX with `data` at the address 0 and `stack` cells for the heap and the stack.
+    pub fn new(data: &[Cell], stack: usize) -> TestAmx {
+        TestAmx::make(data, stack, false)
+    }
+
+    /// Makes an AMX like `new` does, but its header tables keep names in the entries (`AMX_FUNCSTUB`)
+    /// instead of a name table, as older compilers do. Names are limited to 19 characters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::testing::TestAmx;
+    /// use samp_sdk::types::AMX_FUNCSTUB;
+    ///
+    /// let mut test = TestAmx::with_inline_names(&[], 16);
+    /// test.add_public("OnGameModeInit", |_, _| 1);
+    ///
+    /// let amx = test.amx();
+    /// let header = amx.header();
+    ///
+    /// assert_eq!(unsafe { (*header).defsize } as usize, std::mem::size_of::<AMX_FUNCSTUB>());
+    /// assert_eq!(amx.find_public("OnGameModeInit").unwrap(), 0);
+    /// ```
+    pub fn with_inline_names(data: &[Cell], stack: usize) -> TestAmx {
+        TestAmx::make(data, stack, true)
+    }
+
+    fn make(data: &[Cell], stack: usize, inline_names: bool) -> TestAmx {
+        install();
+
+        let mut memory = data.to_vec();
+        memory.resize(data.len() + stack, 0);
+
+        let memory = Box::into_raw(memory.into_boxed_slice());
+        let raw = Box::into_raw(Box::new(MaybeUninit::<types::AMX>::zeroed())) as *mut types::AMX;
+
+        let hea = size_of_val(data) as Cell;
+        let stp = ((data.len() + stack) * size_of::<Cell>()) as Cell;
+
+        unsafe {
+            (*raw).data = (*memory).as_mut_ptr() as *mut u8;
+            (*raw).hea = hea;
+            (*raw).hlw = hea;
+            (*raw).stk = stp;
+            (*raw).stp = stp;
+            (*raw).reset_hea = hea;
+            (*raw).reset_stk = stp;
+        }
+
+        let mut test = TestAmx {
+            raw,
+            header: Box::into_raw(Vec::<Cell>::new().into_boxed_slice()),
+            memory,
+            tables: Tables::default(),
+            inline_names,
+        };
+
+        test.build_header();
+        test
+    }
+
+    /// Returns the AMX to pass to natives.
+    pub fn amx(&self) -> AMX {
+        AMX::new(self.raw)
+    }
+
+    /// Returns the whole memory: the data section, the heap and the stack.
+    pub fn memory(&self) -> &[Cell] {
+        unsafe { &*self.memory }
+    }
+
+    /// Returns the whole memory to change it between calls.
+    pub fn memory_mut(&mut self) -> &mut [Cell] {
+        unsafe { &mut *self.memory }
+    }
+
+    /// Adds a public which `find_public` and `exec` can find, returns its index.
+    pub fn add_public<F>(&mut self, name: &str, public: F) -> i32
+        where F: Fn(&AMX, &[Cell]) -> Cell + Send + Sync + 'static
+    {
+        publics.lock().unwrap()
+            .entry(self.raw as usize)
+            .or_default()
+            .push(Arc::new(public));
+
+        self.tables.publics.push(table_name(name));
+        self.build_header();
+        self.tables.publics.len() as i32 - 1
+    }
+
+    /// Adds a native which the script uses, returns its index.
+    ///
+    /// The native is unresolved until `AMX::register` gives a function with the same name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use samp_sdk::testing::TestAmx;
+    ///
+    /// let mut test = TestAmx::new(&[], 16);
+    /// test.add_native("SetPlayerHealth");
+    /// test.add_native("Kick");
+    ///
+    /// let amx = test.amx();
+    /// assert_eq!(amx.find_native("Kick").unwrap(), 1);
+    /// assert_eq!(amx.get_native(0).unwrap().to_str(), Ok("SetPlayerHealth"));
+    /// assert!(amx.find_native("Ban").is_err());
+    /// ```
+    pub fn add_native(&mut self, name: &str) -> i32 {
+        self.tables.natives.push((table_name(name), 0));
+        self.build_header();
+        self.tables.natives.len() as i32 - 1
+    }
+
+    /// Adds a public variable at an address of the data section, returns its index.
+    pub fn add_pubvar(&mut self, name: &str, address: Cell) -> i32 {
+        self.tables.pubvars.push((table_name(name), address as Ucell));
+        self.build_header();
+        self.tables.pubvars.len() as i32 - 1
+    }
+
+    /// Adds a tag with its id, returns its index.
+    pub fn add_tag(&mut self, name: &str, tag_id: Cell) -> i32 {
+        self.tables.tags.push((table_name(name), tag_id as Ucell));
+        self.build_header();
+        self.tables.tags.len() as i32 - 1
+    }
+
+    /// Calls a native with given arguments, like the script does.
+    ///
+    /// Arguments are cells: values or addresses in AMX memory.
+    pub fn call_native(&mut self, native: AmxNative, args: &[Cell]) -> Cell {
+        let mut params = params(args);
+        native(self.raw, params.as_mut_ptr())
+    }
+
+    /// Takes lines printed with `log!` and `logger` since the last call.
+    ///
+    /// The output is shared by all `TestAmx` of the process, like `logprintf` is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use] extern crate samp_sdk;
+    /// use samp_sdk::testing::TestAmx;
+    ///
+    /// # fn main() {
+    /// let test = TestAmx::new(&[], 16);
+    ///
+    /// log!("{} players online", 3);
+    /// assert_eq!(test.take_log(), vec!["3 players online"]);
+    /// assert!(test.take_log().is_empty());
+    /// # }
+    /// ```
+    pub fn take_log(&self) -> Vec<String> {
+        output.lock()
+            .map(|mut lines| lines.drain(..).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the error raised by the last native, if any, and clears it.
+    pub fn take_error(&mut self) -> Option<AmxError> {
+        unsafe {
+            let error = (*self.raw).error;
+            (*self.raw).error = AMX_ERR_NONE;
+
+            if error == AMX_ERR_NONE {
+                None
+            } else {
+                Some(AmxError::from(error))
+            }
+        }
+    }
+
+    /// Lays out the header, its tables and the name table the way the compiler does.
+    ///
+    /// It's made again after each change of the tables, addresses of registered natives are kept.
+    fn build_header(&mut self) {
+        unsafe {
+            if !(*self.raw).base.is_null() {
+                let natives = table(self.raw, natives_table);
+
+                for (native, stub) in self.tables.natives.iter_mut().zip(natives.iter()) {
+                    native.1 = stub.address();
+                }
+            }
+        }
+
+        let entries = self.tables.publics.iter().map(|name| (name, 0))
+            .chain(self.tables.natives.iter().map(|(name, address)| (name, *address)))
+            .chain(self.tables.pubvars.iter().map(|(name, address)| (name, *address)))
+            .chain(self.tables.tags.iter().map(|(name, tag_id)| (name, *tag_id)))
+            .collect::<Vec<_>>();
+
+        let stub_size = if self.inline_names {
+            size_of::<AMX_FUNCSTUB>()
+        } else {
+            size_of::<FUNCSTUBNT>()
+        };
+
+        let offset = |entries: usize| (size_of::<types::AMX_HEADER>() + entries * stub_size) as i32;
+
+        let nametable = offset(entries.len()) as usize;
+        let names: usize = if self.inline_names {
+            0
+        } else {
+            // The name table starts with the maximum length of a name.
+            size_of::<u16>() + entries.iter().map(|(name, _)| name.as_bytes_with_nul().len()).sum::<usize>()
+        };
+        let cells = (nametable + names).div_ceil(size_of::<Cell>());
+
+        let header = Box::into_raw(vec![0 as Cell; cells].into_boxed_slice());
+
+        unsafe {
+            let base = (*header).as_mut_ptr() as *mut u8;
+            let stubs = base.add(offset(0) as usize);
+            let mut nameofs = nametable + size_of::<u16>();
+
+            if !self.inline_names {
+                *(base.add(nametable) as *mut u16) = AMX_NAME_MAX as u16;
+            }
+
+            for (index, (name, address)) in entries.iter().enumerate() {
+                let bytes = name.as_bytes_with_nul();
+                let stub = stubs.add(index * stub_size);
+
+                if self.inline_names {
+                    let mut stub_name = [0; 20];
+                    assert!(bytes.len() <= stub_name.len(), "{:?} is too long for AMX_FUNCSTUB", name);
+
+                    ptr::copy_nonoverlapping(bytes.as_ptr() as *const c_char, stub_name.as_mut_ptr(), bytes.len());
+
+                    ptr::write_unaligned(stub as *mut AMX_FUNCSTUB, AMX_FUNCSTUB {
+                        address: *address,
+                        name: stub_name,
+                    });
+                } else {
+                    ptr::copy_nonoverlapping(bytes.as_ptr(), base.add(nameofs), bytes.len());
+
+                    ptr::write_unaligned(stub as *mut FUNCSTUBNT, FUNCSTUBNT {
+                        address: *address,
+                        nameofs: nameofs as u32,
+                    });
+
+                    nameofs += bytes.len();
+                }
+            }
+
+            // There is no code and the data section is in `memory`, but the sizes are the same.
+            let dat = (cells * size_of::<Cell>()) as i32;
+            let amx_header = base as *mut types::AMX_HEADER;
+
+            (*amx_header).magic = AMX_MAGIC;
+            (*amx_header).defsize = stub_size as i16;
+            (*amx_header).cod = dat;
+            (*amx_header).dat = dat;
+            (*amx_header).hea = dat + (*self.raw).reset_hea;
+            (*amx_header).stp = dat + (*self.raw).stp;
+            (*amx_header).size = (*amx_header).hea;
+            (*amx_header).publics = offset(0);
+            (*amx_header).natives = offset(self.tables.publics.len());
+            (*amx_header).libraries = offset(self.tables.publics.len() + self.tables.natives.len());
+            (*amx_header).pubvars = (*amx_header).libraries;
+            (*amx_header).tags = offset(entries.len() - self.tables.tags.len());
+            (*amx_header).nametable = nametable as i32;
+
+            (*self.raw).base = base;
+            drop(Box::from_raw(self.header));
+        }
+
+        self.header = header;
+    }
+}
+
+impl Drop for TestAmx {
+    fn drop(&mut self) {
+        if let Ok(mut list) = publics.lock() {
+            list.remove(&(self.raw as usize));
+        }
+
+        unsafe {
+            drop(Box::from_raw(self.raw as *mut MaybeUninit<types::AMX>));
+            drop(Box::from_raw(self.header));
+            drop(Box::from_raw(self.memory));
+        }
+    }
+}
+
+/// Makes native params from arguments: the size of arguments in bytes, then the arguments.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::testing;
+///
+/// assert_eq!(testing::params(&[10, 20]), vec![8, 10, 20]);
+/// ```
+pub fn params(args: &[Cell]) -> Vec<Cell> {
+    let mut params = Vec::with_capacity(args.len() + 1);
+
+    params.push(size_of_val(args) as Cell);
+    params.extend_from_slice(args);
+    params
+}
+
+/// Makes plugin data to pass to `Load` of `new_plugin!`, with the functions of `TestAmx`.
+///
+/// Natives of `define_native!` call the loaded plugin, so a test loads it first.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use] extern crate samp_sdk;
+/// use samp_sdk::amx::{AMX, AmxResult};
+/// use samp_sdk::testing::{self, TestAmx};
+/// use samp_sdk::types::Cell;
+///
+/// struct MyPlugin;
+///
+/// impl MyPlugin {
+///     fn load(&self) -> bool { true }
+///     fn unload(&self) {}
+///     fn amx_load(&self, amx: &AMX) -> Cell { 0 }
+///     fn amx_unload(&self, amx: &AMX) -> Cell { 0 }
+///
+///     fn add(&self, _: &AMX, a: i32, b: i32) -> AmxResult<Cell> {
+///         Ok(a + b)
+///     }
+/// }
+///
+/// impl Default for MyPlugin {
+///     fn default() -> MyPlugin {
+///         MyPlugin {}
+///     }
+/// }
+///
+/// new_plugin!(MyPlugin);
+///
+/// // native: Add(a, b);
+/// define_native!(add, a: i32, b: i32);
+///
+/// # fn main() {
+/// assert!(unsafe { Load(testing::plugin_data().as_ptr()) });
+///
+/// let mut test = TestAmx::new(&[], 16);
+/// assert_eq!(test.call_native(add, &[2, 3]), 5);
+/// # }
+/// ```
+pub fn plugin_data() -> Vec<*const u32> {
+    install();
+
+    let mut data = vec![null(); PLUGIN_DATA_CALLPUBLIC_GM as usize + 1];
+
+    if let Ok(printf) = logprintf.lock() {
+        data[PLUGIN_DATA_LOGPRINTF as usize] = *printf as *const u32;
+    }
+
+    data[PLUGIN_DATA_AMX_EXPORTS as usize] = unsafe { amx_functions } as *const u32;
+    data
+}
+
+/// Makes an unpacked string with the null terminator, to put it in the data of `TestAmx`.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::testing;
+///
+/// assert_eq!(testing::string("Hi"), vec![0x48, 0x69, 0]);
+/// ```
+pub fn string(string: &str) -> Vec<Cell> {
+    string.bytes()
+        .map(Cell::from)
+        .chain(Some(0))
+        .collect()
+}
+
+fn install() {
+    unsafe {
+        if amx_functions.is_null() {
+            amx_functions = exports.as_ptr();
+        }
+    }
+
+    if let Ok(mut printf) = logprintf.lock() {
+        if *printf as usize == 0 {
+            // The SDK always passes a ready string as the only argument.
+            *printf = unsafe { transmute::<extern "C" fn(*const c_char), types::Logprintf_t>(capture) };
+        }
+    }
+}
+
+extern "C" fn capture(text: *const c_char) {
+    let text = unsafe { CStr::from_ptr(text) }.to_string_lossy().into_owned();
+
+    if let Ok(mut lines) = output.lock() {
+        lines.push(text);
+    }
+}
+
+unsafe fn address(amx: *mut types::AMX, address: Cell) -> Option<*mut Cell> {
+    let outside = address < 0 || address >= (*amx).stp
+        || address >= (*amx).hea && address < (*amx).stk
+        || address % size_of::<Cell>() as Cell != 0;
+
+    if outside {
+        None
+    } else {
+        Some((*amx).data.add(address as usize) as *mut Cell)
+    }
+}
+
+fn publics_table(header: &types::AMX_HEADER) -> (i32, i32) {
+    (header.publics, header.natives)
+}
+
+fn natives_table(header: &types::AMX_HEADER) -> (i32, i32) {
+    (header.natives, header.libraries)
+}
+
+fn pubvars_table(header: &types::AMX_HEADER) -> (i32, i32) {
+    (header.pubvars, header.tags)
+}
+
+fn tags_table(header: &types::AMX_HEADER) -> (i32, i32) {
+    (header.tags, header.nametable)
+}
+
+/// An entry of a header table, `FUNCSTUBNT` or `AMX_FUNCSTUB` depending on `defsize`.
+///
+/// Both start with the address, entries are packed, so they are read and written unaligned.
+struct Stub {
+    base: *mut u8,
+    entry: *mut u8,
+    inline_name: bool,
+}
+
+impl Stub {
+    unsafe fn address(&self) -> Ucell {
+        ptr::read_unaligned(self.entry as *const Ucell)
+    }
+
+    unsafe fn set_address(&self, address: Ucell) {
+        ptr::write_unaligned(self.entry as *mut Ucell, address);
+    }
+
+    unsafe fn name<'a>(&self) -> &'a CStr {
+        let name = self.entry.add(size_of::<Ucell>());
+
+        if self.inline_name {
+            CStr::from_ptr(name as *const c_char)
+        } else {
+            let nameofs = ptr::read_unaligned(name as *const u32);
+            CStr::from_ptr(self.base.add(nameofs as usize) as *const c_char)
+        }
+    }
+}
+
+/// Returns entries of a header table, which ends where the next one starts.
+unsafe fn table(amx: *mut types::AMX, bounds: fn(&types::AMX_HEADER) -> (i32, i32)) -> Vec<Stub> {
+    let base = (*amx).base;
+    let header = &*(base as *const types::AMX_HEADER);
+    let (start, end) = bounds(header);
+    let defsize = header.defsize as usize;
+    let count = (end - start) as usize / defsize;
+
+    (0..count)
+        .map(|index| Stub {
+            base,
+            entry: base.add(start as usize + index * defsize),
+            inline_name: defsize == size_of::<AMX_FUNCSTUB>(),
+        })
+        .collect()
+}
+
+unsafe fn find_entry(amx: *mut types::AMX, bounds: fn(&types::AMX_HEADER) -> (i32, i32), name: *const c_char) -> Option<Stub> {
+    let name = CStr::from_ptr(name);
+    table(amx, bounds).into_iter().find(|stub| stub.name() == name)
+}
+
+/// Copies a name like `strcpy`, the SDK gives buffers of `AMX_NAME_MAX + 1` bytes.
+unsafe fn copy_name(stub: &Stub, dest: *mut c_char) {
+    let name = stub.name().to_bytes_with_nul();
+    ptr::copy_nonoverlapping(name.as_ptr() as *const c_char, dest, name.len());
+}
+
+fn table_name(name: &str) -> CString {
+    assert!(name.len() <= AMX_NAME_MAX, "{} is longer than AMX_NAME_MAX", name);
+    CString::new(name).unwrap()
+}
+
+/// Converts a value of a little-endian AMX file to the host order, like the server does.
+extern "C" fn align16(value: *mut u16) -> *mut u16 {
+    unsafe {
+        *value = u16::from_le(*value);
+    }
+
+    value
+}
+
+extern "C" fn align32(value: *mut u32) -> *mut u32 {
+    unsafe {
+        *value = u32::from_le(*value);
+    }
+
+    value
+}
+
+extern "C" fn allot(amx: *mut types::AMX, cells: i32, amx_addr: *mut Cell, phys_addr: *mut *mut Cell) -> i32 {
+    unsafe {
+        let bytes = cells.checked_mul(size_of::<Cell>() as Cell).unwrap_or(Cell::MAX);
+
+        if cells < 0 || (*amx).stk - (*amx).hea - STACK_MARGIN < bytes {
+            return AMX_ERR_MEMORY;
+        }
+
+        *amx_addr = (*amx).hea;
+        *phys_addr = (*amx).data.add((*amx).hea as usize) as *mut Cell;
+        (*amx).hea += bytes;
+    }
+
+    AMX_ERR_NONE
+}
+
+extern "C" fn release(amx: *mut types::AMX, amx_addr: Cell) -> i32 {
+    unsafe {
+        if amx_addr >= (*amx).hlw && amx_addr < (*amx).hea {
+            (*amx).hea = amx_addr;
+        }
+    }
+
+    AMX_ERR_NONE
+}
+
+extern "C" fn push(amx: *mut types::AMX, value: Cell) -> i32 {
+    unsafe {
+        if (*amx).stk - size_of::<Cell>() as Cell - STACK_MARGIN < (*amx).hea {
+            return AMX_ERR_STACKERR;
+        }
+
+        (*amx).stk -= size_of::<Cell>() as Cell;
+        *((*amx).data.add((*amx).stk as usize) as *mut Cell) = value;
+        (*amx).paramcount += 1;
+    }
+
+    AMX_ERR_NONE
+}
+
+extern "C" fn get_addr(amx: *mut types::AMX, amx_addr: Cell, phys_addr: *mut *mut Cell) -> i32 {
+    unsafe {
+        match address(amx, amx_addr) {
+            Some(cell) => {
+                *phys_addr = cell;
+                AMX_ERR_NONE
+            }
+            None => AMX_ERR_MEMACCESS,
+        }
+    }
+}
+
+extern "C" fn str_len(string: *const Cell, length: *mut i32) -> i32 {
+    unsafe {
+        let mut len = 0;
+
+        if packed::is_packed(*string) {
+            while (*string.add(len / size_of::<Cell>())).to_be_bytes()[len % size_of::<Cell>()] != 0 {
+                len += 1;
+            }
+        } else {
+            while *string.add(len) != 0 {
+                len += 1;
+            }
+        }
+
+        *length = len as i32;
+    }
+
+    AMX_ERR_NONE
+}
+
+/// Writes at most `size - 1` characters and the null character, like the server does.
+extern "C" fn set_string(dest: *mut Cell, source: *const c_char, pack: i32, _use_wchar: i32, size: usize) -> i32 {
+    unsafe {
+        let bytes = CStr::from_ptr(source).to_bytes();
+        let bytes = &bytes[..bytes.len().min(size.saturating_sub(1))];
+
+        let cells = if pack != 0 {
+            packed::pack(bytes)
+        } else {
+            bytes.iter().map(|&byte| Cell::from(byte)).chain(Some(0)).collect()
+        };
+
+        if size > 0 {
+            ptr::copy_nonoverlapping(cells.as_ptr(), dest, cells.len());
+        }
+    }
+
+    AMX_ERR_NONE
+}
+
+extern "C" fn raise_error(amx: *mut types::AMX, error: i32) -> i32 {
+    unsafe {
+        (*amx).error = error;
+    }
+
+    AMX_ERR_NONE
+}
+
+extern "C" fn num_publics(amx: *mut types::AMX, number: *mut i32) -> i32 {
+    unsafe {
+        *number = table(amx, publics_table).len() as i32;
+    }
+
+    AMX_ERR_NONE
+}
+
+extern "C" fn num_natives(amx: *mut types::AMX, number: *mut i32) -> i32 {
+    unsafe {
+        *number = table(amx, natives_table).len() as i32;
+    }
+
+    AMX_ERR_NONE
+}
+
+extern "C" fn num_pubvars(amx: *mut types::AMX, number: *mut i32) -> i32 {
+    unsafe {
+        *number = table(amx, pubvars_table).len() as i32;
+    }
+
+    AMX_ERR_NONE
+}
+
+extern "C" fn num_tags(amx: *mut types::AMX, number: *mut i32) -> i32 {
+    unsafe {
+        *number = table(amx, tags_table).len() as i32;
+    }
+
+    AMX_ERR_NONE
+}
+
+extern "C" fn find_public(amx: *mut types::AMX, name: *const c_char, index: *mut i32) -> i32 {
+    unsafe {
+        let name = CStr::from_ptr(name);
+
+        match table(amx, publics_table).iter().position(|stub| stub.name() == name) {
+            Some(position) => {
+                *index = position as i32;
+                AMX_ERR_NONE
+            }
+            None => AMX_ERR_NOTFOUND,
+        }
+    }
+}
+
+extern "C" fn find_native(amx: *mut types::AMX, name: *const c_char, index: *mut i32) -> i32 {
+    unsafe {
+        let name = CStr::from_ptr(name);
+
+        match table(amx, natives_table).iter().position(|stub| stub.name() == name) {
+            Some(position) => {
+                *index = position as i32;
+                AMX_ERR_NONE
+            }
+            None => AMX_ERR_NOTFOUND,
+        }
+    }
+}
+
+extern "C" fn find_pubvar(amx: *mut types::AMX, name: *const c_char, amx_addr: *mut Cell) -> i32 {
+    unsafe {
+        match find_entry(amx, pubvars_table, name) {
+            Some(stub) => {
+                *amx_addr = stub.address() as Cell;
+                AMX_ERR_NONE
+            }
+            None => AMX_ERR_NOTFOUND,
+        }
+    }
+}
+
+extern "C" fn find_tag_id(amx: *mut types::AMX, tag_id: Cell, name: *mut c_char) -> i32 {
+    unsafe {
+        match table(amx, tags_table).iter().find(|stub| stub.address() as Cell == tag_id) {
+            Some(stub) => {
+                copy_name(stub, name);
+                AMX_ERR_NONE
+            }
+            None => AMX_ERR_NOTFOUND,
+        }
+    }
+}
+
+extern "C" fn get_native(amx: *mut types::AMX, index: i32, name: *mut c_char) -> i32 {
+    unsafe {
+        match table(amx, natives_table).get(index as usize) {
+            Some(stub) if index >= 0 => {
+                copy_name(stub, name);
+                AMX_ERR_NONE
+            }
+            _ => AMX_ERR_INDEX,
+        }
+    }
+}
+
+extern "C" fn get_tag(amx: *mut types::AMX, index: i32, name: *mut c_char, tag_id: *mut Cell) -> i32 {
+    unsafe {
+        match table(amx, tags_table).get(index as usize) {
+            Some(stub) if index >= 0 => {
+                copy_name(stub, name);
+                *tag_id = stub.address() as Cell;
+                AMX_ERR_NONE
+            }
+            _ => AMX_ERR_INDEX,
+        }
+    }
+}
+
+extern "C" fn register(amx: *mut types::AMX, list: *const types::AMX_NATIVE_INFO, number: i32) -> i32 {
+    let mut error = AMX_ERR_NONE;
+
+    unsafe {
+        for stub in table(amx, natives_table).iter().filter(|stub| stub.address() == 0) {
+            let name = stub.name();
+
+            // A negative number means the list ends with a null name.
+            let found = (0..)
+                .take_while(|&index| number < 0 || index < number)
+                .map(|index| *list.add(index as usize))
+                .take_while(|info| !info.name.is_null())
+                .find(|info| CStr::from_ptr(info.name) == name);
+
+            match found {
+                Some(info) => stub.set_address(info.func as usize as Ucell),
+                None => error = AMX_ERR_NOTFOUND,
+            }
+        }
+    }
+
+    error
+}
+
+extern "C" fn mem_info(amx: *mut types::AMX, codesize: *mut i32, datasize: *mut i32, stackheap: *mut i32) -> i32 {
+    unsafe {
+        let header = (*amx).base as *const types::AMX_HEADER;
+
+        *codesize = (*header).dat - (*header).cod;
+        *datasize = (*header).hea - (*header).dat;
+        *stackheap = (*header).stp - (*header).hea;
+    }
+
+    AMX_ERR_NONE
+}
+
+extern "C" fn flags(amx: *mut types::AMX, flags: *mut u16) -> i32 {
+    unsafe {
+        *flags = (*((*amx).base as *const types::AMX_HEADER)).flags as u16;
+    }
+
+    AMX_ERR_NONE
+}
+
+extern "C" fn clone(clone: *mut types::AMX, source: *mut types::AMX, data: *mut c_void) -> i32 {
+    unsafe {
+        let header = (*source).base as *const types::AMX_HEADER;
+        let hea = (*header).hea - (*header).dat;
+        let stp = (*header).stp - (*header).dat;
+
+        ptr::copy_nonoverlapping((*source).data, data as *mut u8, hea as usize);
+
+        *clone = *source;
+        (*clone).data = data as *mut u8;
+        (*clone).cip = 0;
+        (*clone).error = AMX_ERR_NONE;
+        (*clone).paramcount = 0;
+        (*clone).hea = hea;
+        (*clone).hlw = hea;
+        (*clone).reset_hea = hea;
+        (*clone).stk = stp;
+        (*clone).stp = stp;
+        (*clone).reset_stk = stp;
+    }
+
+    AMX_ERR_NONE
+}
+
+extern "C" fn set_debug_hook(amx: *mut types::AMX, debug: types::AmxDebug) -> i32 {
+    unsafe {
+        (*amx).debug = debug;
+    }
+
+    AMX_ERR_NONE
+}
+
+extern "C" fn utf8_check(string: *const c_char, length: *mut i32) -> i32 {
+    let string = unsafe { CStr::from_ptr(string) };
+
+    match ::std::str::from_utf8(string.to_bytes()) {
+        Ok(string) => {
+            if !length.is_null() {
+                unsafe {
+                    *length = string.chars().count() as i32;
+                }
+            }
+
+            AMX_ERR_NONE
+        }
+        Err(_) => AMX_ERR_FORMAT,
+    }
+}
+
+extern "C" fn utf8_get(string: *const c_char, endptr: *mut *const c_char, value: *mut Cell) -> i32 {
+    unsafe {
+        let width = match *string as u8 {
+            0x00..=0x7F => 1,
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => return AMX_ERR_PARAMS,
+        };
+
+        // A null byte isn't a continuation byte, so it stops before the end of the string.
+        let mut bytes = Vec::with_capacity(width);
+
+        for index in 0..width {
+            let byte = *string.add(index) as u8;
+
+            if index > 0 && byte & 0xC0 != 0x80 {
+                return AMX_ERR_PARAMS;
+            }
+
+            bytes.push(byte);
+        }
+
+        match ::std::str::from_utf8(&bytes).ok().and_then(|decoded| decoded.chars().next()) {
+            Some(character) => {
+                *value = character as Cell;
+                *endptr = string.add(width);
+                AMX_ERR_NONE
+            }
+            None => AMX_ERR_PARAMS,
+        }
+    }
+}
+
+extern "C" fn utf8_put(string: *mut c_char, endptr: *mut *mut c_char, maxchars: i32, value: Cell) -> i32 {
+    let mut buffer = [0; 4];
+
+    let encoded = match char::from_u32(value as u32) {
+        Some(character) => character.encode_utf8(&mut buffer).as_bytes(),
+        None => return AMX_ERR_DOMAIN,
+    };
+
+    if encoded.len() > maxchars.max(0) as usize {
+        return AMX_ERR_DOMAIN;
+    }
+
+    unsafe {
+        ptr::copy_nonoverlapping(encoded.as_ptr() as *const c_char, string, encoded.len());
+        *endptr = string.add(encoded.len());
+    }
+
+    AMX_ERR_NONE
+}
+
+extern "C" fn exec(amx: *mut types::AMX, retval: *mut Cell, index: i32) -> i32 {
+    let public = publics.lock().unwrap()
+        .get(&(amx as usize))
+        .and_then(|entries| entries.get(index as usize))
+        .cloned();
+
+    // Arguments are popped even if there is no such public.
+    let args = unsafe {
+        let count = (*amx).paramcount as usize;
+        let first = (*amx).data.add((*amx).stk as usize) as *const Cell;
+        let args = slice::from_raw_parts(first, count).to_vec();
+
+        (*amx).stk += (count * size_of::<Cell>()) as Cell;
+        (*amx).paramcount = 0;
+
+        args
+    };
+
+    let public = match public {
+        Some(public) if index >= 0 => public,
+        _ => return AMX_ERR_INDEX,
+    };
+
+    unsafe {
+        // The hook is null until the SDK sets it.
+        let debug = ptr::read_unaligned(ptr::addr_of!((*amx).debug) as *const Option<types::AmxDebug>);
+
+        if let Some(debug) = debug {
+            let error = debug(amx);
+
+            if error != AMX_ERR_NONE {
+                return error;
+            }
+        }
+    }
+
+    let result = public(&AMX::new(amx), &args);
+
+    unsafe {
+        // An error raised by a native called from the public aborts the script.
+        let error = (*amx).error;
+
+        if error != AMX_ERR_NONE {
+            (*amx).error = AMX_ERR_NONE;
+            return error;
+        }
+
+        if !retval.is_null() {
+            *retval = result;
+        }
+    }
+
+    AMX_ERR_NONE
+}
@@ -1,11 +1,179 @@
 /*!
     List of AMX types such as `Cell`, `AMX_NATIVE_INFO` and signatures of functions.
 */
+use std::convert::TryFrom;
 use std::os::raw::c_void;
+use std::time::Duration;
+
+use crate::amx::AmxError;
+use crate::consts::samp;
 
 pub type Cell = i32;
 pub type Ucell = u32;
 
+/// A `Float:` cell.
+///
+/// Has the same layout as `Cell`, so it can be used as an argument in `define_native!`
+/// (including `ref Float`) and pushed with `AMX::push`.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::types::{Cell, Float};
+///
+/// let cell: Cell = Float(-0.0).into();
+/// assert_eq!(cell as u32, 0x8000_0000);
+///
+/// let nan = Float::from(0x7fc0_0001);
+/// assert!(nan.0.is_nan());
+/// assert_eq!(Cell::from(nan), 0x7fc0_0001);
+/// ```
+#[repr(transparent)]
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Default)]
+pub struct Float(pub f32);
+
+impl From<Cell> for Float {
+    fn from(cell: Cell) -> Float {
+        Float(f32::from_bits(cell as u32))
+    }
+}
+
+impl From<Float> for Cell {
+    fn from(float: Float) -> Cell {
+        float.0.to_bits() as Cell
+    }
+}
+
+/// Narrows `f64` to a `Float:` value, the precision above `f32` is lost.
+///
+/// # Examples
+///
+/// ```
+/// use samp_sdk::types::{Cell, Float};
+///
+/// let cell = Cell::from(Float(1.0));
+/// let wide = f64::from(Float::from(cell));
+///
+/// assert_eq!(wide, 1.0);
+/// assert_eq!(Cell::from(Float::from(wide)), cell);
+/// # assert_eq!(Float::from(0.1f64), Float(0.1f32));
+/// ```
+impl From<f64> for Float {
+    fn from(value: f64) -> Float {
+        Float(value as f32)
+    }
+}
+
+impl From<Float> for f64 {
+    fn from(float: Float) -> f64 {
+        f64::from(float.0)
+    }
+}
+
+/// An id of a player.
+///
+/// Made from a cell with `TryFrom`, which accepts ids of connected players
+/// and `INVALID_PLAYER_ID`, other values are `AmxError::Domain`.
+///
+/// # Examples
+///
+/// ```
+/// use std::convert::TryFrom;
+/// use samp_sdk::types::PlayerId;
+/// use samp_sdk::amx::AmxError;
+///
+/// let player = PlayerId::try_from(12).unwrap();
+/// assert!(player.is_valid());
+/// assert_eq!(player.raw(), 12);
+///
+/// let invalid = PlayerId::try_from(0xFFFF).unwrap();
+/// assert!(!invalid.is_valid());
+///
+/// match PlayerId::try_from(1000) {
+///     Err(AmxError::Domain) => (),
+///     _ => panic!("1000 is out of range"),
+/// }
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct PlayerId(pub u16);
+
+impl PlayerId {
+    pub const MAX_PLAYERS: u16 = samp::MAX_PLAYERS as u16;
+    pub const INVALID: PlayerId = PlayerId(samp::INVALID_PLAYER_ID as u16);
+
+    /// Checks if the id is in range of player slots, i.e. not `INVALID_PLAYER_ID`.
+    pub fn is_valid(self) -> bool {
+        self.0 < PlayerId::MAX_PLAYERS
+    }
+
+    pub fn raw(self) -> u16 {
+        self.0
+    }
+}
+
+impl TryFrom<Cell> for PlayerId {
+    type Error = AmxError;
+
+    fn try_from(cell: Cell) -> Result<PlayerId, AmxError> {
+        if cell >= 0 && cell < Cell::from(PlayerId::MAX_PLAYERS) || cell == Cell::from(PlayerId::INVALID.0) {
+            Ok(PlayerId(cell as u16))
+        } else {
+            Err(AmxError::Domain)
+        }
+    }
+}
+
+impl From<PlayerId> for Cell {
+    fn from(player: PlayerId) -> Cell {
+        Cell::from(player.0)
+    }
+}
+
+/// Converts milliseconds passed by a script (e.g. an interval of `SetTimer`) to `Duration`.
+///
+/// Returns `None` for negative values.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use samp_sdk::types::millis_to_duration;
+///
+/// assert_eq!(millis_to_duration(-1), None);
+/// assert_eq!(millis_to_duration(0), Some(Duration::from_millis(0)));
+/// assert_eq!(millis_to_duration(1500), Some(Duration::from_millis(1500)));
+/// ```
+pub fn millis_to_duration(cell: Cell) -> Option<Duration> {
+    if cell < 0 {
+        None
+    } else {
+        Some(Duration::from_millis(cell as u64))
+    }
+}
+
+/// Converts `Duration` to milliseconds in a cell, saturating at `Cell::MAX`.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use samp_sdk::types::{Cell, duration_to_millis};
+///
+/// assert_eq!(duration_to_millis(Duration::from_millis(0)), 0);
+/// assert_eq!(duration_to_millis(Duration::from_micros(1500)), 1);
+/// assert_eq!(duration_to_millis(Duration::from_secs(60)), 60_000);
+/// assert_eq!(duration_to_millis(Duration::from_secs(u64::MAX)), Cell::MAX);
+/// ```
+pub fn duration_to_millis(duration: Duration) -> Cell {
+    let millis = duration.as_millis();
+
+    if millis > Cell::MAX as u128 {
+        Cell::MAX
+    } else {
+        millis as Cell
+    }
+}
+
 #[repr(C, packed)]
 #[derive(Copy, Clone)]
 pub struct AMX {
@@ -80,7 +248,7 @@ pub struct AMX_HEADER {
 
 pub type Align16 = extern "C" fn(*mut u16) -> *mut u16;
 pub type Align32 = extern "C" fn(*mut u32) -> *mut u32;
-pub type Allot = extern "C" fn(*mut AMX, i32, *mut Cell, *mut Cell) -> i32;
+pub type Allot = extern "C" fn(*mut AMX, i32, *mut Cell, *mut *mut Cell) -> i32;
 pub type Callback = extern "C" fn(*mut AMX, Cell, *mut Cell, *mut Cell) -> i32;
 pub type Cleanup = extern "C" fn(*mut AMX) -> i32;
 pub type Clone = extern "C" fn(*mut AMX, *mut AMX, *mut c_void) -> i32;